@@ -1,63 +1,177 @@
 use async_std::task;
+use clap::{App, Arg};
+use rustbox::{Color, RustBox, RB_NORMAL};
 
 use adventofcode_2019::grid::*;
 use adventofcode_2019::intcode_computer::*;
+use adventofcode_2019::ocr;
+use adventofcode_2019::raster::*;
 
 use std::convert::{Into, TryFrom};
 use std::default::Default;
-use std::{fmt, io, mem};
+use std::thread;
+use std::time::Duration;
+use std::{fmt, io};
+
+const FRAME_DELAY: Duration = Duration::from_millis(10);
 
 fn main() {
+    let matches = App::new("Hull painting robot")
+        .version("1.0")
+        .arg(
+            Arg::with_name("animate")
+                .help("watch the robot paint")
+                .short("a")
+                .long("animate"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("where to save the registration identifier, as a .png or .pbm image")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("scale")
+                .help("size in pixels of each panel in the saved image")
+                .short("s")
+                .long("scale")
+                .value_name("N")
+                .default_value("8")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("colors")
+                .help("hex colours to use for white and black panels in the saved image")
+                .short("c")
+                .long("colors")
+                .value_name("WHITE,BLACK")
+                .default_value("ffffff,000000")
+                .takes_value(true),
+        )
+        .get_matches();
+    let scale: usize = matches
+        .value_of("scale")
+        .unwrap()
+        .parse()
+        .ok()
+        .filter(|scale| *scale > 0)
+        .expect("scale must be a whole number of at least 1");
+    let colors: Vec<Rgb> = matches
+        .value_of("colors")
+        .unwrap()
+        .split(',')
+        .map(|color| parse_color(color).expect("invalid colour"))
+        .collect();
+    let (white, black) = match colors[..] {
+        [white, black] => (white, black),
+        _ => panic!("expected two colours"),
+    };
+
     let mut line = String::new();
     let _ = io::stdin().read_line(&mut line).unwrap();
     let input = line.trim().to_string();
 
-    let grid = task::block_on(run_painting(&input, PanelColor::Black.into()));
-    println!("Part 1: {}", grid.len());
+    let rustbox = if matches.is_present("animate") {
+        Some(RustBox::init(Default::default()).expect("failed creating rustbox"))
+    } else {
+        None
+    };
+    let paint = |initial: PanelColor| {
+        task::block_on(run_painting(&input, initial.into(), |grid, robot| {
+            if let Some(rustbox) = &rustbox {
+                draw(rustbox, grid, robot);
+            }
+        }))
+    };
+
+    let grid = paint(PanelColor::Black);
+    let part_1 = grid.len();
+    let grid = paint(PanelColor::White);
+    drop(rustbox);
+
+    println!("Part 1: {}", part_1);
+    match ocr::recognize(&grid, |panel| matches!(panel, PanelColor::White)) {
+        Some(text) => println!("Part 2: {}\n{}", text, grid),
+        None => println!("Part 2: \n{}", grid),
+    }
 
-    let grid = task::block_on(run_painting(&input, PanelColor::White.into()));
-    println!("Part 2: \n{}", grid);
+    if let Some(path) = matches.value_of("output") {
+        let raster = Raster::from_grid(&grid, scale, |panel| match panel {
+            Some(PanelColor::White) => white,
+            _ => black,
+        });
+        raster.save(path).expect("failed saving image");
+    }
 }
 
 type PanelGrid = Grid<PanelColor>;
 
-async fn run_painting(program: &str, initial_input: Int) -> PanelGrid {
+fn draw(rustbox: &RustBox, grid: &PanelGrid, robot: &HullPaintingRobot) {
+    // Shifted so the hull starts below the status line
+    let mut bounds = grid.clone();
+    bounds.insert(robot.pos.clone(), PanelColor::Black);
+    let ((min_x, min_y), _) = bounds.bounds();
+    let shift = |Coord(x, y): &Coord| Coord(x - min_x, y - min_y + 1);
+
+    let mut frame: Grid<String> = Grid::new();
+    for (coord, color) in grid.iter() {
+        frame.insert(shift(coord), color.to_string());
+    }
+    frame.insert(shift(&robot.pos), robot.facing.to_string());
+
+    rustbox.clear();
+    frame.draw(rustbox);
+    rustbox.print(
+        0,
+        0,
+        RB_NORMAL,
+        Color::White,
+        Color::Black,
+        &format!("painted: {}", grid.len()),
+    );
+    rustbox.present();
+    thread::sleep(FRAME_DELAY);
+}
+
+/// Runs the robot until it's done, calling `on_step` after each panel it
+/// paints and moves on from.
+async fn run_painting<F>(program: &str, initial_input: Int, mut on_step: F) -> PanelGrid
+where
+    F: FnMut(&PanelGrid, &HullPaintingRobot),
+{
     let ((input_sender, input_receiver), (output_sender, output_receiver)) =
         IntcodeComputer::create_io();
-    let mut computer = IntcodeComputer::new(&input_receiver, &output_sender);
-
+    let mut computer = Machine::new();
     computer.init(program).unwrap();
+    let computer = IntcodeProcess::new(computer, input_receiver, output_sender).spawn();
 
-    let t = task::spawn(async move {
-        let mut robot = HullPaintingRobot::default();
-        let mut grid = PanelGrid::new();
+    let mut robot = HullPaintingRobot::default();
+    let mut grid = PanelGrid::new();
 
-        input_sender.send(initial_input).await.unwrap();
-        while let Ok(new_color) = output_receiver.recv().await {
-            let turn_input = output_receiver.recv().await.unwrap();
+    input_sender.send(initial_input).await.unwrap();
+    while let Ok(new_color) = output_receiver.recv().await {
+        let turn_input = output_receiver.recv().await.unwrap();
 
-            grid.insert(robot.pos.clone(), PanelColor::try_from(new_color).unwrap());
-            robot.turn(turn_input).unwrap();
-            robot.move_forward();
+        grid.insert(robot.pos.clone(), PanelColor::try_from(new_color).unwrap());
+        robot.turn(turn_input).unwrap();
+        robot.move_forward();
+        on_step(&grid, &robot);
 
-            let panel_color = match grid.get(&robot.pos) {
-                Some(color) => *color,
-                _ => PanelColor::default(),
-            };
+        let panel_color = match grid.get(&robot.pos) {
+            Some(color) => *color,
+            _ => PanelColor::default(),
+        };
 
-            input_sender.send((panel_color).into()).await.unwrap();
+        // The program may have already exited after its final move.
+        if input_sender.send(panel_color.into()).await.is_err() {
+            break;
         }
+    }
 
-        grid
-    });
-
-    task::block_on(async {
-        computer.run().await.unwrap();
-    });
-    mem::drop(computer);
-    mem::drop(output_sender);
-
-    t.await
+    computer.await.unwrap();
+    grid
 }
 
 impl fmt::Display for PanelColor {
@@ -101,6 +215,23 @@ impl HullPaintingRobot {
     }
 }
 
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Direction::*;
+
+        write!(
+            f,
+            "{}",
+            match self {
+                Up => "^",
+                Down => "v",
+                Left => "<",
+                Right => ">",
+            }
+        )
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum Direction {
     Up,
@@ -1,110 +1,108 @@
 use lazy_static::lazy_static;
 use regex::Regex;
 
-use std::cell::RefCell;
-use std::collections::HashSet;
-use std::hash::Hash;
-use std::io::{self, BufRead};
-use std::ops::{Add, AddAssign};
+use std::io::{self, Read};
 use std::str::FromStr;
 
 fn main() {
-    let stdin = io::stdin();
-    let moons: Vec<RefCell<Moon>> = stdin
-        .lock()
-        .lines()
-        .filter_map(|line| Moon::from_str(&line.unwrap()).map(|m| RefCell::new(m)).ok())
-        .collect();
-
-    let p1_moons = moons.clone();
-    for _ in 0..1000 {
-        simulate_universe(p1_moons.as_slice());
-    }
-
-    let total_energy = p1_moons
-        .iter()
-        .fold(0, |acc, moon| acc + moon.borrow().energy());
-
-    println!("Part 1: {}", total_energy);
-
-    // Part 2 is not finished. While this works for the simple example, it is not efficient enough
-    // for the second example or actual inputs.
-
-    let mut states: HashSet<Vec<Moon>> = HashSet::new();
-
-    while states.insert(moons.iter().map(|moon| moon.borrow().clone()).collect()) {
-        simulate_universe(moons.as_slice());
-    }
-
-    println!("Part 2: {}", states.len());
-}
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input).unwrap();
+    let universe = Universe::from_str(&input).expect("failed parsing moons");
 
-fn simulate_universe(moons: &[RefCell<Moon>]) {
-    for moon in moons.iter() {
-        for other_moon in moons.iter() {
-            if other_moon != moon {
-                moon.borrow_mut().apply_gravity(&other_moon.borrow());
-            }
-        }
+    let mut p1_universe = universe.clone();
+    for _ in 0..1000 {
+        p1_universe.step();
     }
+    println!("Part 1: {}", p1_universe.energy());
 
-    for moon in moons.iter() {
-        moon.borrow_mut().apply_velocity();
-    }
+    println!("Part 2: {}", universe.period());
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct Moon {
-    position: AxesTriple,
-    velocity: AxesTriple,
+/// One axis of every moon. The axes don't affect each other, so each can
+/// be simulated on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Axis {
+    positions: Vec<i64>,
+    velocities: Vec<i64>,
 }
 
-impl Moon {
-    fn new(x: i32, y: i32, z: i32) -> Self {
-        Moon {
-            position: AxesTriple { x, y, z },
-            velocity: AxesTriple::default(),
+impl Axis {
+    fn new(positions: Vec<i64>) -> Self {
+        Axis {
+            velocities: vec![0; positions.len()],
+            positions,
         }
     }
 
-    fn apply_gravity(&mut self, other: &Moon) {
-        if self.position.x < other.position.x {
-            self.velocity.x += 1;
-        } else if self.position.x > other.position.x {
-            self.velocity.x -= 1;
+    fn step(&mut self) {
+        for (i, velocity) in self.velocities.iter_mut().enumerate() {
+            for other in self.positions.iter() {
+                *velocity += (other - self.positions[i]).signum();
+            }
         }
 
-        if self.position.y < other.position.y {
-            self.velocity.y += 1;
-        } else if self.position.y > other.position.y {
-            self.velocity.y -= 1;
+        for (position, velocity) in self.positions.iter_mut().zip(self.velocities.iter()) {
+            *position += velocity;
         }
+    }
 
-        if self.position.z < other.position.z {
-            self.velocity.z += 1;
-        } else if self.position.z > other.position.z {
-            self.velocity.z -= 1;
+    /// Steps until the axis is back how it started. Every state has exactly
+    /// one state before it, so the first one to repeat is the initial one.
+    fn period(&self) -> u64 {
+        let mut axis = self.clone();
+        let mut steps = 0;
+        loop {
+            axis.step();
+            steps += 1;
+            if axis == *self {
+                return steps;
+            }
         }
     }
+}
 
-    fn apply_velocity(&mut self) {
-        self.position += self.velocity;
-    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Universe {
+    axes: [Axis; 3],
+}
 
-    fn potential_energy(&self) -> u32 {
-        self.position.x.abs() as u32 + self.position.y.abs() as u32 + self.position.z.abs() as u32
+impl Universe {
+    fn step(&mut self) {
+        for axis in self.axes.iter_mut() {
+            axis.step();
+        }
     }
 
-    fn kinetic_energy(&self) -> u32 {
-        self.velocity.x.abs() as u32 + self.velocity.y.abs() as u32 + self.velocity.z.abs() as u32
+    fn energy(&self) -> u64 {
+        let moons = self.axes[0].positions.len();
+        (0..moons)
+            .map(|i| {
+                let potential: u64 = self
+                    .axes
+                    .iter()
+                    .map(|a| a.positions[i].unsigned_abs())
+                    .sum();
+                let kinetic: u64 = self
+                    .axes
+                    .iter()
+                    .map(|a| a.velocities[i].unsigned_abs())
+                    .sum();
+                potential * kinetic
+            })
+            .sum()
     }
 
-    fn energy(&self) -> u32 {
-        self.potential_energy() * self.kinetic_energy()
+    /// Steps until the whole universe is back how it started, which is once
+    /// every axis is at the same time.
+    fn period(&self) -> u64 {
+        self.axes
+            .iter()
+            .map(|axis| axis.period())
+            .fold(1, |acc, period| acc / gcd(acc, period) * period)
     }
 }
 
-impl FromStr for Moon {
+impl FromStr for Universe {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -112,48 +110,74 @@ impl FromStr for Moon {
             static ref RE: Regex = Regex::new(r"<x=(?P<x>.+), y=(?P<y>.+), z=(?P<z>.+)>").unwrap();
         }
 
-        if let Some(caps) = RE.captures(s) {
-            Ok(Moon::new(
-                caps.name("x").unwrap().as_str().parse().unwrap(),
-                caps.name("y").unwrap().as_str().parse().unwrap(),
-                caps.name("z").unwrap().as_str().parse().unwrap(),
-            ))
-        } else {
-            Err(Error::InvalidInputError)
+        let mut positions = [Vec::new(), Vec::new(), Vec::new()];
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let caps = RE.captures(line).ok_or(Error::InvalidInputError)?;
+            for (axis, name) in positions.iter_mut().zip(["x", "y", "z"]) {
+                let position = caps[name]
+                    .trim()
+                    .parse()
+                    .map_err(|_| Error::InvalidInputError)?;
+                axis.push(position);
+            }
         }
+
+        let [x, y, z] = positions;
+        Ok(Universe {
+            axes: [Axis::new(x), Axis::new(y), Axis::new(z)],
+        })
     }
 }
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
-struct AxesTriple {
-    x: i32,
-    y: i32,
-    z: i32,
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
 }
 
-impl Add for AxesTriple {
-    type Output = Self;
+#[derive(Debug)]
+enum Error {
+    InvalidInputError,
+}
 
-    fn add(self, other: Self) -> Self {
-        Self {
-            x: self.x + other.x,
-            y: self.y + other.y,
-            z: self.z + other.z,
-        }
+#[test]
+fn first_example_works() {
+    let universe = Universe::from_str(
+        "<x=-1, y=0, z=2>
+         <x=2, y=-10, z=-7>
+         <x=4, y=-8, z=8>
+         <x=3, y=5, z=-1>",
+    )
+    .unwrap();
+
+    let mut stepped = universe.clone();
+    for _ in 0..10 {
+        stepped.step();
     }
+    assert_eq!(stepped.axes[0].positions, vec![2, 1, 3, 2]);
+    assert_eq!(stepped.axes[1].velocities, vec![-2, 1, 2, -1]);
+    assert_eq!(stepped.energy(), 179);
+
+    assert_eq!(universe.period(), 2772);
 }
 
-impl AddAssign for AxesTriple {
-    fn add_assign(&mut self, other: Self) {
-        *self = Self {
-            x: self.x + other.x,
-            y: self.y + other.y,
-            z: self.z + other.z,
-        }
+#[test]
+fn second_example_works() {
+    let universe = Universe::from_str(
+        "<x=-8, y=-10, z=0>
+         <x=5, y=5, z=10>
+         <x=2, y=-7, z=3>
+         <x=9, y=-8, z=-3>",
+    )
+    .unwrap();
+
+    let mut stepped = universe.clone();
+    for _ in 0..100 {
+        stepped.step();
     }
-}
+    assert_eq!(stepped.energy(), 1940);
 
-#[derive(Debug)]
-enum Error {
-    InvalidInputError,
+    assert_eq!(universe.period(), 4686774924);
 }
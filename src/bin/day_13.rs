@@ -1,7 +1,3 @@
-use async_std::{
-    sync::{Arc, RwLock},
-    task,
-};
 use clap::{App, Arg};
 use rustbox::{Color, Event, Key, RustBox, RB_NORMAL};
 
@@ -11,7 +7,14 @@ use adventofcode_2019::intcode_computer::*;
 use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::time::Duration;
-use std::{fmt, fs, io, mem, str::FromStr};
+use std::{fmt, fs, io, str::FromStr};
+
+/// Save slots, bound to F1-F9 for saving and 1-9 for loading.
+const SAVE_SLOTS: usize = 9;
+/// How many of the most recent frames are kept to rewind through.
+const REWIND_HISTORY: usize = 200;
+/// Frames gone back by each press of backspace.
+const REWIND_FRAMES: usize = 10;
 
 fn main() {
     let matches = App::new("Intcode arcade brick breaker")
@@ -32,15 +35,28 @@ fn main() {
                 .value_name("FILE")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("autopilot")
+                .help("move the paddle under the ball instead of reading the keyboard")
+                .short("a")
+                .long("autopilot"),
+        )
+        .arg(
+            Arg::with_name("headless")
+                .help("play on autopilot without drawing the screen")
+                .long("headless"),
+        )
         .get_matches();
+    let headless = matches.is_present("headless");
+    let autopilot = headless || matches.is_present("autopilot");
 
     let mut line = String::new();
     let _ = io::stdin().read_line(&mut line).unwrap();
     let mut input = line.trim().to_string();
 
-    let (grid, _inputs) = play(&input, vec![0].into());
+    let (game, _inputs) = play(&input, vec![0].into(), autopilot, headless);
 
-    let num_blocks = grid.values().fold(
+    let num_blocks = game.grid.values().fold(
         0,
         |acc, tile| if *tile == Tile::Block { acc + 1 } else { acc },
     );
@@ -59,106 +75,224 @@ fn main() {
     };
 
     input.replace_range(..1, "2");
-    let (_grid, inputs) = play(&input, loaded_inputs);
+    let (game, inputs) = play(&input, loaded_inputs, autopilot, headless);
+    println!("Part 2: {}", game.score);
 
     if let Some(outpath) = matches.value_of("inputs-out") {
         fs::write(outpath, IntVec(inputs).to_string()).unwrap();
     }
 }
 
-fn play(program: &str, mut loaded_inputs: VecDeque<Int>) -> (GameGrid, Vec<Int>) {
-    let ((in_sender, in_receiver), (out_sender, out_receiver)) = IntcodeComputer::create_io();
-    let mut computer = IntcodeComputer::new(&in_receiver, &out_sender);
-    computer.init(program).unwrap();
-
-    let rustbox = Arc::new(RustBox::init(Default::default()).expect("failed creating rustbox"));
-    let run = Arc::new(RwLock::new(false));
-
-    let rb = rustbox.clone();
-    let running = run.clone();
-
-    let io = task::spawn(async move {
-        let mut inputs: Vec<Int> = Vec::new();
-        let wait_duration = Duration::from_millis(500);
-
-        while *running.read().await {
-            if let Some(input) = match loaded_inputs.pop_front() {
-                Some(input) => Some(input),
-                None => {
-                    if cfg!(feature = "slowgamemode") {
-                        if let Ok(Event::KeyEvent(key)) = rb.poll_event(false) {
-                            get_input(key)
-                        } else {
-                            None
-                        }
-                    } else {
-                        if let Ok(Event::KeyEvent(key)) = rb.peek_event(wait_duration, false) {
-                            get_input(key)
-                        } else {
-                            Some(0)
-                        }
-                    }
+/// The screen, score and where the ball and paddle are, as drawn by the
+/// program so far.
+#[derive(Debug, Clone, Default)]
+struct Game {
+    grid: GameGrid,
+    score: Int,
+    ball: Option<Coord>,
+    paddle: Option<Coord>,
+}
+
+impl Game {
+    fn update(&mut self, x: Int, y: Int, value: Int) {
+        if x == -1 && y == 0 {
+            self.score = value;
+            return;
+        }
+
+        let tile = Tile::try_from(value).expect("failed creating tile");
+        match tile {
+            Tile::Ball => self.ball = Some(Coord(x, y)),
+            Tile::HorizontalPaddle => self.paddle = Some(Coord(x, y)),
+            _ => {}
+        }
+        self.grid.insert(Coord(x, y), tile);
+    }
+
+    /// Tilts the joystick towards the ball, so the paddle stays under it.
+    fn autopilot(&self) -> Int {
+        match (&self.ball, &self.paddle) {
+            (Some(ball), Some(paddle)) => (ball.0 - paddle.0).signum(),
+            _ => 0,
+        }
+    }
+
+    fn draw(&self, rustbox: &RustBox) {
+        self.grid.draw(rustbox);
+        rustbox.print(
+            0,
+            0,
+            RB_NORMAL,
+            Color::White,
+            Color::Black,
+            &self.score.to_string(),
+        );
+        rustbox.present();
+    }
+}
+
+/// Everything needed to pick the game back up from a frame.
+#[derive(Debug, Clone)]
+struct SaveState {
+    machine: Snapshot,
+    game: Game,
+    inputs: Vec<Int>,
+}
+
+/// What a key press asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Joystick(Int),
+    Save(usize),
+    Load(usize),
+    Rewind,
+    Quit,
+}
+
+/// Runs the game until it exits, taking joystick input from
+/// `loaded_inputs` until they run out and then from the autopilot or the
+/// keyboard. Returns the final game and the inputs that reached the highest
+/// score.
+///
+/// When playing from the keyboard, the game can be saved to and loaded from
+/// slots or rewound a few frames at a time, including after it's over if
+/// there's anywhere to go back to.
+fn play(
+    program: &str,
+    mut loaded_inputs: VecDeque<Int>,
+    autopilot: bool,
+    headless: bool,
+) -> (Game, Vec<Int>) {
+    let mut machine = Machine::new();
+    machine.init(program).unwrap();
+
+    let rustbox = if headless {
+        None
+    } else {
+        Some(RustBox::init(Default::default()).expect("failed creating rustbox"))
+    };
+    let interactive = rustbox.is_some() && !autopilot;
+    let mut inputs: Vec<Int> = Vec::new();
+    let mut game = Game::default();
+    let mut screen = Vec::with_capacity(3);
+
+    let mut slots: Vec<Option<SaveState>> = vec![None; SAVE_SLOTS];
+    let mut recent: VecDeque<SaveState> = VecDeque::new();
+    let mut best: Option<(Int, Vec<Int>)> = None;
+
+    loop {
+        let status = machine.resume().expect("failed running computer");
+        if let Status::Output(val) = status {
+            screen.push(val);
+            if let [x, y, value] = screen[..] {
+                game.update(x, y, value);
+                screen.clear();
+                if let Some(rustbox) = &rustbox {
+                    game.draw(rustbox);
                 }
-            } {
-                inputs.push(input);
-                in_sender.send(input).await.unwrap();
-            };
+            }
+            continue;
         }
 
-        inputs
-    });
-
-    let rb = rustbox.clone();
-    let t = task::spawn(async move {
-        let mut grid = GameGrid::new();
-        let mut score = 0;
-
-        while let Ok(x) = out_receiver.recv().await {
-            let y = out_receiver.recv().await.expect("failed getting y");
-            let value = out_receiver
-                .recv()
-                .await
-                .expect("failed getting output value");
-            if x == -1 && y == 0 {
-                score = value;
-            } else {
-                let tile = Tile::try_from(value).expect("failed creating tile");
-                grid.insert(Coord(x, y), tile);
+        if status == Status::Halted {
+            if best.as_ref().is_none_or(|(score, _)| game.score > *score) {
+                best = Some((game.score, inputs.clone()));
+            }
+            // The game's over for good unless there's a save or a recent
+            // frame to go back to.
+            let can_go_back = !recent.is_empty() || slots.iter().any(Option::is_some);
+            if !interactive || !can_go_back {
+                break;
             }
+        } else if let Some(input) = loaded_inputs.pop_front() {
+            inputs.push(input);
+            machine.push_input(input);
+            continue;
+        } else if !interactive {
+            let input = game.autopilot();
+            inputs.push(input);
+            machine.push_input(input);
+            continue;
+        }
+        let rustbox = rustbox.as_ref().expect("interactive play needs a screen");
 
-            grid.draw(&rb);
-            rb.print(
-                0,
-                0,
-                RB_NORMAL,
-                Color::White,
-                Color::Black,
-                &score.to_string(),
-            );
-            rb.present();
+        let save = SaveState {
+            machine: machine.snapshot(),
+            game: game.clone(),
+            inputs: inputs.clone(),
+        };
+        if status == Status::AwaitingInput {
+            recent.push_back(save.clone());
+            if recent.len() > REWIND_HISTORY {
+                recent.pop_front();
+            }
         }
 
-        grid
-    });
+        let load = match read_key(rustbox, status == Status::Halted) {
+            Command::Joystick(input) if status == Status::AwaitingInput => {
+                inputs.push(input);
+                machine.push_input(input);
+                None
+            }
+            // Any other key leaves the game over screen
+            Command::Joystick(_) => break,
+            Command::Save(slot) => {
+                slots[slot] = Some(save);
+                None
+            }
+            Command::Load(slot) => slots[slot].clone(),
+            Command::Rewind => {
+                let target = recent.len().saturating_sub(REWIND_FRAMES + 1);
+                recent.truncate(target + 1);
+                recent.pop_back()
+            }
+            Command::Quit => break,
+        };
+
+        if let Some(state) = load {
+            machine.restore(&state.machine);
+            game = state.game;
+            inputs = state.inputs;
+            rustbox.clear();
+            game.draw(rustbox);
+        }
+    }
 
-    let running = run.clone();
-    task::block_on(async {
-        *running.write().await = true;
-        computer.run().await.expect("failed running computer");
-        *running.write().await = false;
-    });
-    mem::drop(computer);
-    mem::drop(out_sender);
+    match best {
+        Some((score, best)) if score > game.score => (game, best),
+        _ => (game, inputs),
+    }
+}
 
-    (task::block_on(t), task::block_on(io))
+/// Reads a key press, waiting for one if `wait` is set or the
+/// `slowgamemode` feature is enabled. Otherwise the joystick is left in
+/// the middle if nothing is pressed for a while.
+fn read_key(rustbox: &RustBox, wait: bool) -> Command {
+    if wait || cfg!(feature = "slowgamemode") {
+        loop {
+            if let Ok(Event::KeyEvent(key)) = rustbox.poll_event(false) {
+                return get_command(key);
+            }
+        }
+    } else {
+        match rustbox.peek_event(Duration::from_millis(500), false) {
+            Ok(Event::KeyEvent(key)) => get_command(key),
+            _ => Command::Joystick(0),
+        }
+    }
 }
 
-fn get_input(key: Key) -> Option<Int> {
+fn get_command(key: Key) -> Command {
     match key {
-        Key::Right => Some(1),
-        Key::Left => Some(-1),
-        Key::Ctrl('c') => std::process::exit(0),
-        _ => Some(0),
+        Key::Right => Command::Joystick(1),
+        Key::Left => Command::Joystick(-1),
+        Key::F(n) if (1..=SAVE_SLOTS as u32).contains(&n) => Command::Save(n as usize - 1),
+        Key::Char(c @ '1'..='9') if c.to_digit(10).unwrap() as usize <= SAVE_SLOTS => {
+            Command::Load(c.to_digit(10).unwrap() as usize - 1)
+        }
+        Key::Backspace => Command::Rewind,
+        Key::Char('q') | Key::Ctrl('c') => Command::Quit,
+        _ => Command::Joystick(0),
     }
 }
 
@@ -217,3 +351,49 @@ impl fmt::Display for Tile {
 enum Error {
     InvalidInput(Int),
 }
+
+#[test]
+fn autopilot_follows_the_ball() {
+    let mut game = Game::default();
+    assert_eq!(game.autopilot(), 0);
+
+    game.update(5, 10, 3);
+    game.update(2, 7, 4);
+    game.update(-1, 0, 42);
+    assert_eq!(game.score, 42);
+    assert_eq!(game.autopilot(), -1);
+
+    game.update(8, 8, 4);
+    assert_eq!(game.autopilot(), 1);
+    game.update(5, 9, 4);
+    assert_eq!(game.autopilot(), 0);
+}
+
+#[test]
+fn keys_map_to_commands() {
+    assert_eq!(get_command(Key::Left), Command::Joystick(-1));
+    assert_eq!(get_command(Key::F(3)), Command::Save(2));
+    assert_eq!(get_command(Key::Char('3')), Command::Load(2));
+    assert_eq!(get_command(Key::Char('0')), Command::Joystick(0));
+    assert_eq!(get_command(Key::F(12)), Command::Joystick(0));
+    assert_eq!(get_command(Key::Backspace), Command::Rewind);
+    assert_eq!(get_command(Key::Char('q')), Command::Quit);
+}
+
+#[test]
+fn halted_games_ignore_leftover_inputs() {
+    // Draws a paddle and halts without asking for any input
+    let (game, inputs) = play("104,1,104,2,104,3,99", vec![0].into(), true, true);
+    assert_eq!(game.paddle, Some(Coord(1, 2)));
+    assert!(inputs.is_empty());
+
+    // Shows its only input as the score
+    let (game, inputs) = play(
+        "3,100,104,-1,104,0,4,100,99",
+        vec![5, 6, 7].into(),
+        true,
+        true,
+    );
+    assert_eq!(game.score, 5);
+    assert_eq!(inputs, vec![5]);
+}
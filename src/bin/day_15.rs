@@ -0,0 +1,73 @@
+use clap::{App, Arg};
+use rustbox::{Color, RustBox, RB_NORMAL};
+
+use adventofcode_2019::grid::*;
+use adventofcode_2019::intcode_computer::*;
+use adventofcode_2019::repair_droid::*;
+
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+const FRAME_DELAY: Duration = Duration::from_millis(5);
+
+fn main() {
+    let matches = App::new("Oxygen system repair droid")
+        .version("1.0")
+        .arg(
+            Arg::with_name("visualize")
+                .help("watch the droid explore the area")
+                .short("v")
+                .long("visualize"),
+        )
+        .get_matches();
+
+    let mut line = String::new();
+    let _ = io::stdin().read_line(&mut line).unwrap();
+    let input = line.trim().to_string();
+
+    let mut droid = Machine::new();
+    droid.init(&input).unwrap();
+
+    let area = if matches.is_present("visualize") {
+        let rustbox = RustBox::init(Default::default()).expect("failed creating rustbox");
+        let area = explore(&mut droid, |area| draw(&rustbox, area));
+        drop(rustbox);
+        area
+    } else {
+        explore(&mut droid, |_| {})
+    }
+    .expect("failed exploring the area");
+
+    println!("{}", area.map);
+    println!(
+        "Part 1: {}",
+        area.oxygen_distance().expect("no oxygen system found")
+    );
+    println!("Part 2: {}", area.fill_time().unwrap());
+}
+
+fn draw(rustbox: &RustBox, area: &Area) {
+    // Shifted so the map starts below the status line
+    let ((min_x, min_y), _) = area.map.bounds();
+    let shift = |Coord(x, y): &Coord| Coord(x - min_x, y - min_y + 1);
+
+    let mut frame: Grid<String> = Grid::new();
+    for (coord, tile) in area.map.iter() {
+        frame.insert(shift(coord), tile.to_string());
+    }
+    frame.insert(shift(&area.droid), "D".to_string());
+
+    rustbox.clear();
+    frame.draw(rustbox);
+    rustbox.print(
+        0,
+        0,
+        RB_NORMAL,
+        Color::White,
+        Color::Black,
+        &format!("explored: {}", area.map.len()),
+    );
+    rustbox.present();
+    thread::sleep(FRAME_DELAY);
+}
@@ -2,8 +2,15 @@ use async_std::task;
 
 use adventofcode_2019::grid::*;
 use adventofcode_2019::intcode_computer::*;
+use adventofcode_2019::intcode_io::*;
 
-use std::{io, mem};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+
+/// Most characters allowed in the main routine and each movement function,
+/// not counting the trailing newline.
+const MAX_ROUTINE_LEN: usize = 20;
 
 fn main() {
     let mut line = String::new();
@@ -31,6 +38,10 @@ fn main() {
 
     let p1: i64 = intersections.into_iter().map(alignment_parameter).sum();
     println!("Part 1: {}", p1);
+
+    let path = trace_path(&grid);
+    let routines = compress(&path).expect("no way to fit the path into three functions");
+    println!("Part 2: {}", collect_dust(&input, &routines));
 }
 
 fn alignment_parameter(Coord(x, y): &Coord) -> i64 {
@@ -44,36 +55,287 @@ fn is_scaffold(grid: &TileGrid, x: i64, y: i64) -> bool {
 type TileGrid = Grid<char>;
 
 async fn run_program(program: &str) -> TileGrid {
-    let ((_input_sender, input_receiver), (output_sender, output_receiver)) =
-        IntcodeComputer::create_io();
-    let mut computer = IntcodeComputer::new(&input_receiver, &output_sender);
-
+    let mut computer = Machine::new();
     computer.init(program).unwrap();
 
-    let t = task::spawn(async move {
-        let mut grid = TileGrid::new();
-        let mut x: Int = 0;
-        let mut y: Int = 0;
-        while let Ok(output) = output_receiver.recv().await {
-            let c: char = (output as u8).into();
-            match c {
-                '\n' => {
-                    x = 0;
-                    y += 1;
-                }
-                c => {
-                    grid.insert(Coord(x, y), c);
-                    x += 1;
-                }
+    let mut camera = AsciiGridOutput::new();
+    IntcodeProcess::new(computer, VecDeque::new(), &mut camera)
+        .run()
+        .await
+        .unwrap();
+    camera.grid
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Move {
+    Left,
+    Right,
+    Forward(usize),
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Move::Left => write!(f, "L"),
+            Move::Right => write!(f, "R"),
+            Move::Forward(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+/// A main routine calling movement functions A, B and C, by index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Routines {
+    main: Vec<usize>,
+    functions: Vec<Vec<Move>>,
+}
+
+impl Routines {
+    #[allow(dead_code)]
+    fn expand(&self) -> Vec<Move> {
+        self.main
+            .iter()
+            .flat_map(|f| self.functions[*f].iter().copied())
+            .collect()
+    }
+
+    /// The lines to type into the robot, in the order it asks for them.
+    fn lines(&self) -> Vec<String> {
+        let main: Vec<String> = self
+            .main
+            .iter()
+            .map(|f| ((b'A' + *f as u8) as char).to_string())
+            .collect();
+
+        let mut lines = vec![main.join(",")];
+        for i in 0..3 {
+            let function = self.functions.get(i).map(Vec::as_slice).unwrap_or(&[]);
+            lines.push(routine(function));
+        }
+        lines
+    }
+}
+
+fn routine(moves: &[Move]) -> String {
+    moves
+        .iter()
+        .map(|m| m.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn find_robot(grid: &TileGrid) -> Option<(Coord, (i64, i64))> {
+    grid.iter().find_map(|(coord, c)| {
+        let dir = match c {
+            '^' => (0, -1),
+            'v' => (0, 1),
+            '<' => (-1, 0),
+            '>' => (1, 0),
+            _ => return None,
+        };
+        Some((coord.clone(), dir))
+    })
+}
+
+/// Follows the scaffold from the robot to its far end, going straight
+/// across intersections and turning only when the scaffold does. The robot
+/// may start off facing along the scaffold, or away from it.
+fn trace_path(grid: &TileGrid) -> Vec<Move> {
+    let (mut pos, mut dir) = find_robot(grid).expect("no robot on the scaffold");
+    let mut path = Vec::new();
+
+    loop {
+        let ahead = dir;
+        let left = (dir.1, -dir.0);
+        let right = (-dir.1, dir.0);
+        let behind = (-dir.0, -dir.1);
+        let scaffold = |(x, y): (i64, i64)| is_scaffold(grid, pos.0 + x, pos.1 + y);
+
+        // Only the first leg can carry straight on or head back the way
+        // the robot faces, since every later one starts where the last
+        // ran out of scaffold.
+        let first = path.is_empty();
+        let turns = if first && scaffold(ahead) {
+            vec![]
+        } else if scaffold(left) {
+            dir = left;
+            vec![Move::Left]
+        } else if scaffold(right) {
+            dir = right;
+            vec![Move::Right]
+        } else if first && scaffold(behind) {
+            dir = behind;
+            vec![Move::Right, Move::Right]
+        } else {
+            return path;
+        };
+
+        let mut steps = 0;
+        while is_scaffold(grid, pos.0 + dir.0, pos.1 + dir.1) {
+            pos = Coord(pos.0 + dir.0, pos.1 + dir.1);
+            steps += 1;
+        }
+        path.extend(turns);
+        path.push(Move::Forward(steps));
+    }
+}
+
+/// Splits `path` into at most three functions that fit the robot's memory.
+/// An empty path has nothing to split.
+fn compress(path: &[Move]) -> Option<Routines> {
+    if path.is_empty() {
+        return None;
+    }
+    let mut routines = Routines {
+        main: Vec::new(),
+        functions: Vec::new(),
+    };
+    if compress_from(path, &mut routines) {
+        Some(routines)
+    } else {
+        None
+    }
+}
+
+fn compress_from(path: &[Move], routines: &mut Routines) -> bool {
+    if path.is_empty() {
+        return true;
+    }
+    // Each call takes a letter and a comma, except the last one's comma.
+    if routines.main.len() * 2 + 1 > MAX_ROUTINE_LEN {
+        return false;
+    }
+
+    for f in 0..routines.functions.len() {
+        if path.starts_with(&routines.functions[f]) {
+            let len = routines.functions[f].len();
+            routines.main.push(f);
+            if compress_from(&path[len..], routines) {
+                return true;
             }
+            routines.main.pop();
         }
+    }
+
+    if routines.functions.len() < 3 {
+        let f = routines.functions.len();
+        for len in (1..=path.len()).rev() {
+            if routine(&path[..len]).len() > MAX_ROUTINE_LEN {
+                continue;
+            }
+            routines.functions.push(path[..len].to_vec());
+            routines.main.push(f);
+            if compress_from(&path[len..], routines) {
+                return true;
+            }
+            routines.main.pop();
+            routines.functions.pop();
+        }
+    }
+
+    false
+}
+
+/// Wakes the robot up, has it run `routines` without the video feed, and
+/// returns the dust it reports.
+fn collect_dust(program: &str, routines: &Routines) -> Int {
+    let mut robot = Machine::new();
+    robot.init(program).unwrap();
+    robot.write(0, 2);
+
+    let mut input = Vec::new();
+    for line in routines.lines().iter().map(String::as_str).chain(["n"]) {
+        input.extend(line.bytes().map(Int::from));
+        input.push(b'\n' as Int);
+    }
+    *robot.run_with_input(input).unwrap().last().unwrap()
+}
+
+#[allow(dead_code)]
+fn parse_grid(s: &str) -> TileGrid {
+    let mut grid = Grid::new();
+    for (y, line) in s.lines().enumerate() {
+        for (x, c) in line.chars().enumerate() {
+            grid.insert(Coord(x as i64, y as i64), c);
+        }
+    }
+    grid
+}
+
+#[test]
+fn traces_and_compresses_the_example_scaffold() {
+    let grid = parse_grid(
+        "#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......",
+    );
+
+    let path = trace_path(&grid);
+    assert_eq!(
+        routine(&path),
+        "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+    );
+
+    let routines = compress(&path).unwrap();
+    assert_eq!(routines.expand(), path);
+    assert!(routines.functions.len() <= 3);
+    for line in routines.lines() {
+        assert!(line.len() <= MAX_ROUTINE_LEN, "{} is too long", line);
+    }
+}
+
+#[test]
+fn rejects_paths_that_dont_fit() {
+    // Forty distinct moves are too long for three functions.
+    let path: Vec<Move> = (1..=40).map(Move::Forward).collect();
+    assert_eq!(compress(&path), None);
+
+    let routines = Routines {
+        main: vec![0, 1, 0],
+        functions: vec![vec![Move::Right, Move::Forward(8)], vec![Move::Left]],
+    };
+    assert_eq!(routines.lines(), vec!["A,B,A", "R,8", "L", ""]);
+}
 
-        grid
-    });
+#[test]
+fn starts_without_turning_when_facing_along_the_scaffold() {
+    let grid = parse_grid(
+        ">####
+....#
+....#",
+    );
+    assert_eq!(
+        trace_path(&grid),
+        vec![Move::Forward(4), Move::Right, Move::Forward(2)]
+    );
 
-    computer.run().await.unwrap();
-    mem::drop(computer);
-    mem::drop(output_sender);
+    let grid = parse_grid(
+        "<####
+....#",
+    );
+    assert_eq!(
+        trace_path(&grid),
+        vec![
+            Move::Right,
+            Move::Right,
+            Move::Forward(4),
+            Move::Right,
+            Move::Forward(1)
+        ]
+    );
 
-    t.await
+    assert_eq!(trace_path(&parse_grid("..\n.^")), vec![]);
+    assert_eq!(compress(&[]), None);
 }
@@ -1,6 +1,5 @@
-use async_std::task;
-
 use adventofcode_2019::intcode_computer::*;
+use adventofcode_2019::intcode_engine::Engine;
 
 use std::io;
 
@@ -13,28 +12,27 @@ fn main() {
     let _ = io::stdin().read_line(&mut line).unwrap();
     let input = line.trim().to_string();
 
-    let (inputs, outputs) = IntcodeComputer::create_io();
-    let mut computer = IntcodeComputer::new(&inputs.1, &outputs.0);
+    let program = Engine::new(&input).unwrap();
 
     {
-        computer.init(&input).unwrap();
+        let mut computer = program.clone();
         computer.write(1, 12);
         computer.write(2, 2);
-        task::block_on(computer.run()).unwrap();
+        computer.resume().unwrap();
 
-        println!("Part 1: {}", computer.read(0));
+        println!("Part 1: {}", computer.peek(0));
     }
 
     let mut noun = 0;
     let mut verb = 0;
 
     loop {
-        computer.init(&input).unwrap();
+        let mut computer = program.clone();
         computer.write(1, noun);
         computer.write(2, verb);
-        task::block_on(computer.run()).unwrap();
+        computer.resume().unwrap();
 
-        if computer.read(0) == EXPECTED_OUTPUT {
+        if computer.peek(0) == EXPECTED_OUTPUT {
             break;
         } else {
             if verb == MAX_INPUT {
@@ -1,5 +1,3 @@
-use async_std::{prelude::*, task};
-
 use adventofcode_2019::intcode_computer::*;
 
 use std::io;
@@ -9,20 +7,13 @@ fn main() {
     let _ = io::stdin().read_line(&mut line).unwrap();
     let input = line.trim().to_string();
 
-    let (inputs, outputs) = IntcodeComputer::create_io();
-    let mut computer = IntcodeComputer::new(&inputs.1, &outputs.0);
+    let mut computer = Machine::new();
 
     computer.init(&input).unwrap();
-    let (_, output) = task::block_on(computer.run().join(async {
-        (inputs.0).send(1).await.unwrap();
-        outputs.1.recv().await.unwrap()
-    }));
-    println!("Part 1: {}", output);
+    let outputs = computer.run_with_input([1]).unwrap();
+    println!("Part 1: {}", outputs.last().unwrap());
 
     computer.init(&input).unwrap();
-    let (_, output) = task::block_on(computer.run().join(async {
-        (inputs.0).send(5).await.unwrap();
-        (outputs.1).recv().await.unwrap()
-    }));
-    println!("Part 2: {}", output);
+    let outputs = computer.run_with_input([5]).unwrap();
+    println!("Part 2: {}", outputs.last().unwrap());
 }
@@ -1,31 +1,46 @@
-use async_std::channel::bounded;
 use async_std::task;
-
-use futures::future::join_all;
+use clap::{App, Arg};
 use permute::permute;
 
 use adventofcode_2019::intcode_computer::*;
+use adventofcode_2019::intcode_pipeline::*;
 
 use std::io;
 
 const NUM_AMPS: usize = 5;
 
 fn main() {
+    let matches = App::new("Amplifier circuit")
+        .version("1.0")
+        .arg(
+            Arg::with_name("amps")
+                .help("number of amplifiers in the circuit")
+                .short("n")
+                .long("amps")
+                .value_name("N")
+                .takes_value(true),
+        )
+        .get_matches();
+    let num_amps: usize = matches
+        .value_of("amps")
+        .map(|n| n.parse().expect("invalid number of amps"))
+        .unwrap_or(NUM_AMPS);
+
     let mut line = String::new();
     let _ = io::stdin().read_line(&mut line).unwrap();
     let input = line.trim().to_string();
 
-    let phase_options: Vec<Int> = (0..NUM_AMPS).map(|x| x as _).collect();
+    let phase_options: Vec<Int> = (0..num_amps).map(|x| x as _).collect();
     println!("Part 1: {}", get_max_output(&input, &phase_options, 0));
 
-    let phase_options: Vec<Int> = (5..(5 + NUM_AMPS)).map(|x| x as _).collect();
+    let phase_options: Vec<Int> = (5..(5 + num_amps)).map(|x| x as _).collect();
     println!("Part 2: {}", get_max_output(&input, &phase_options, 0));
 }
 
-fn get_max_output(program: &str, phase_options: &Vec<Int>, initial_input: Int) -> Int {
+fn get_max_output(program: &str, phase_options: &[Int], initial_input: Int) -> Int {
     let mut max = 0;
 
-    for permutation in permute(phase_options.clone()) {
+    for permutation in permute(phase_options.to_vec()) {
         let output = task::block_on(run_amps(&permutation, program, initial_input));
         max = max.max(output);
     }
@@ -34,34 +49,15 @@ fn get_max_output(program: &str, phase_options: &Vec<Int>, initial_input: Int) -
 }
 
 async fn run_amps(phase_settings: &[Int], program: &str, initial_input: Int) -> Int {
-    let a_io = bounded(BUFFER_SIZE);
-    let b_io = bounded(BUFFER_SIZE);
-    let c_io = bounded(BUFFER_SIZE);
-    let d_io = bounded(BUFFER_SIZE);
-    let e_io = bounded(BUFFER_SIZE);
+    let mut amp = Machine::new();
+    amp.init(program).unwrap();
 
-    let mut a = IntcodeComputer::new(&a_io.1, &b_io.0);
-    let mut b = IntcodeComputer::new(&b_io.1, &c_io.0);
-    let mut c = IntcodeComputer::new(&c_io.1, &d_io.0);
-    let mut d = IntcodeComputer::new(&d_io.1, &e_io.0);
-    let mut e = IntcodeComputer::new(&e_io.1, &a_io.0);
-
-    (a_io.0).send(phase_settings[0]).await.unwrap();
-    (b_io.0).send(phase_settings[1]).await.unwrap();
-    (c_io.0).send(phase_settings[2]).await.unwrap();
-    (d_io.0).send(phase_settings[3]).await.unwrap();
-    (e_io.0).send(phase_settings[4]).await.unwrap();
-
-    (a_io.0).send(initial_input).await.unwrap();
-
-    let mut amps = vec![&mut a, &mut b, &mut c, &mut d, &mut e];
-    for amp in amps.iter_mut() {
-        amp.init(program).unwrap();
-    }
-    let runnings = amps.iter_mut().map(|a| a.run());
-    join_all(runnings).await;
+    let inputs: Vec<Vec<Int>> = phase_settings.iter().map(|phase| vec![*phase]).collect();
+    let mut pipeline = Pipeline::ring(&amp, &inputs);
+    pipeline.push_input(0, initial_input);
 
-    a_io.1.recv().await.unwrap()
+    let outputs = pipeline.run().await.unwrap();
+    *outputs.last().and_then(|output| output.last()).unwrap()
 }
 
 #[test]
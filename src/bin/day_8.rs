@@ -1,144 +1,128 @@
-use std::{env, fmt, io};
+use clap::{App, Arg};
 
-fn main() {
-    let mut args = env::args();
-    let _ = args.next().unwrap();
-    let width: u32 = args.next().unwrap().parse().unwrap();
-    let height: u32 = args.next().unwrap().parse().unwrap();
-
-    let mut line = String::new();
-    let _ = io::stdin().read_line(&mut line).unwrap();
-    let input = line.trim().to_string();
+use adventofcode_2019::get_input;
+use adventofcode_2019::raster::{self, parse_color};
+use adventofcode_2019::space_image::*;
 
-    let image = Image::new(width, height, &input);
-    println!("Part 1: {}", find_fewest_zeros(&image));
-    println!("Part 2: \n{}", image);
-}
+use std::process;
 
-fn find_fewest_zeros(image: &Image) -> u32 {
-    let mut min = None;
-    let mut min_layer = None;
-    for (i, layer) in image.layers.iter().enumerate() {
-        let num_zeros = layer
-            .iter()
-            .fold(0, |acc, x| if *x == 0 { acc + 1 } else { acc });
-        if let Some(current_min) = min {
-            if num_zeros < current_min {
-                min = Some(num_zeros);
-                min_layer = Some(i);
-            }
-        } else {
-            min = Some(num_zeros);
-            min_layer = Some(i);
-        }
-    }
-
-    let mut ones = 0;
-    let mut twos = 0;
-    for x in image.layers[min_layer.unwrap()].iter() {
-        if *x == 1 {
-            ones += 1;
-        } else if *x == 2 {
-            twos += 1;
+fn main() {
+    let matches = App::new("Space Image Format decoder")
+        .version("1.0")
+        .arg(
+            Arg::with_name("width")
+                .help("width of the image in pixels")
+                .default_value("25")
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("height")
+                .help("height of the image in pixels")
+                .default_value("6")
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("stats")
+                .help("print how many pixels of each colour every layer has")
+                .long("stats"),
+        )
+        .arg(
+            Arg::with_name("encode")
+                .help("read layers drawn as rows of digits, separated by blank lines, and print them as image data")
+                .short("e")
+                .long("encode"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("where to save the decoded image, as a .ppm, .pgm, .pbm or .png image")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("scale")
+                .help("size of each pixel in the saved image")
+                .short("s")
+                .long("scale")
+                .default_value("8"),
+        )
+        .arg(
+            Arg::with_name("colors")
+                .help("colours of white, black and transparent pixels in the saved image")
+                .short("c")
+                .long("colors")
+                .value_name("WHITE,BLACK,TRANSPARENT")
+                .default_value("ffffff,000000,808080"),
+        )
+        .get_matches();
+    let width: usize = matches
+        .value_of("width")
+        .unwrap()
+        .parse()
+        .expect("invalid width");
+    let height: usize = matches
+        .value_of("height")
+        .unwrap()
+        .parse()
+        .expect("invalid height");
+
+    let input = get_input().expect("failed reading input");
+    if matches.is_present("encode") {
+        match Image::parse_drawn(width, height, &input) {
+            Ok(image) => println!("{}", image.encode()),
+            Err(err) => fail(err),
         }
+        return;
     }
 
-    ones * twos
-}
-
-struct Image {
-    width: u32,
-    height: u32,
-    layers: Vec<Vec<u32>>,
-}
-
-impl Image {
-    fn new(width: u32, height: u32, image_data: &str) -> Image {
-        let mut layers = Vec::new();
-        let mut layer = Vec::new();
-
-        let layer_size = (width * height) as usize;
-
-        let chars = image_data.chars().filter(|c| !c.is_whitespace());
-
-        for (i, c) in chars.enumerate() {
-            if i > 0 && i % layer_size == 0 {
-                layers.push(layer);
-                layer = Vec::new();
-            }
+    let image = Image::parse(width, height, &input).unwrap_or_else(|err| fail(err));
 
-            let x: u32 = c.to_digit(10).unwrap();
-            layer.push(x);
-        }
-
-        layers.push(layer);
-
-        Image {
-            width,
-            height,
-            layers,
+    if matches.is_present("stats") {
+        for (i, stats) in image.stats().iter().enumerate() {
+            println!(
+                "Layer {}: {} black, {} white, {} transparent",
+                i, stats.black, stats.white, stats.transparent
+            );
         }
     }
 
-    fn decode(&self) -> String {
-        let mut image = vec![2; (self.width * self.height) as usize];
-
-        for i in 0..image.len() {
-            for layer in self.layers.iter() {
-                if i < layer.len() && layer[i] != 2 {
-                    image[i] = layer[i];
-                    break;
-                }
-            }
-        }
-
-        image.into_iter().map(|x| x.to_string()).collect()
+    let (_, stats) = image.fewest_black();
+    println!("Part 1: {}", stats.checksum());
+    match image.recognize() {
+        Some(text) => println!("Part 2: {}\n{}", text, image),
+        None => println!("Part 2: \n{}", image),
     }
-}
 
-impl fmt::Display for Image {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let decoded = self.decode();
-        let mut lines = Vec::new();
-        let mut s = &decoded[..];
-        while s.len() >= self.width as usize {
-            let (left, right) = s.split_at(self.width as usize);
-            lines.push(left);
-            s = right;
-        }
-        let out: String = lines.join("\n").replace("0", " ").replace("1", "█");
-
-        write!(f, "{}", out)
+    if let Some(path) = matches.value_of("output") {
+        let scale: usize = matches
+            .value_of("scale")
+            .unwrap()
+            .parse()
+            .ok()
+            .filter(|scale| *scale > 0)
+            .expect("scale must be a whole number of at least 1");
+        let colors: Vec<raster::Rgb> = matches
+            .value_of("colors")
+            .unwrap()
+            .split(',')
+            .map(|color| parse_color(color).expect("invalid colour"))
+            .collect();
+        let (white, black, transparent) = match colors[..] {
+            [white, black, transparent] => (white, black, transparent),
+            _ => panic!("expected three colours"),
+        };
+
+        let raster = image.to_raster(scale, |pixel| match pixel {
+            WHITE => white,
+            BLACK => black,
+            _ => transparent,
+        });
+        raster.save(path).expect("failed saving image");
     }
 }
 
-#[test]
-fn example_works() {
-    let image = Image::new(2, 2, "0222112222120000");
-    assert_eq!(&image.decode(), "0110");
-
-    let image = Image::new(2, 2, "2222 2122 00  ");
-    assert_eq!(&image.decode(), "0122");
-
-    let image = Image::new(2, 2, "222221210010");
-    assert_eq!(&image.decode(), "0111");
-
-    let image = Image::new(
-        3,
-        3,
-        "
-		122
-		201
-		211
-
-		002
-		111
-		222
-
-		000
-		000
-		000
-		",
-    );
-    assert_eq!(&image.decode(), "100101011");
+fn fail(err: Error) -> ! {
+    eprintln!("invalid image: {}", err);
+    process::exit(1);
 }
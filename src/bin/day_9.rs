@@ -1,5 +1,3 @@
-use async_std::task;
-
 use adventofcode_2019::intcode_computer::*;
 
 use std::io;
@@ -9,24 +7,13 @@ fn main() {
     let _ = io::stdin().read_line(&mut line).unwrap();
     let input = line.trim().to_string();
 
-    let (inputs, outputs) = IntcodeComputer::create_io();
-    let mut computer = IntcodeComputer::new(&inputs.1, &outputs.0);
+    let mut computer = Machine::new();
 
     computer.init(&input).unwrap();
-
-    let boost_keycode = task::block_on(async {
-        (inputs.0).send(1).await.unwrap();
-        computer.run().await.unwrap();
-        outputs.1.recv().await.unwrap()
-    });
-
-    println!("Part 1: {}", boost_keycode);
+    let boost_keycode = computer.run_with_input([1]).unwrap();
+    println!("Part 1: {}", boost_keycode.last().unwrap());
 
     computer.init(&input).unwrap();
-    let coords = task::block_on(async {
-        (inputs.0).send(2).await.unwrap();
-        computer.run().await.unwrap();
-        outputs.1.recv().await.unwrap()
-    });
-    println!("Part 2: {:?}", coords);
+    let coords = computer.run_with_input([2]).unwrap();
+    println!("Part 2: {:?}", coords.last().unwrap());
 }
@@ -0,0 +1,67 @@
+use async_std::task;
+use clap::{App, Arg};
+
+use adventofcode_2019::intcode_computer::*;
+use adventofcode_2019::intcode_io::*;
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read},
+    process,
+};
+
+fn main() {
+    let matches = App::new("Intcode ASCII console")
+        .version("1.0")
+        .about(
+            "Runs an ASCII-speaking Intcode program, typing each input line followed by a newline",
+        )
+        .arg(
+            Arg::with_name("program")
+                .help("file containing the Intcode program")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("script")
+                .help("file of input lines to type before reading from stdin")
+                .short("s")
+                .long("script")
+                .value_name("FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("batch")
+                .help("stop once the script runs out instead of reading from stdin")
+                .short("b")
+                .long("batch")
+                .requires("script"),
+        )
+        .get_matches();
+
+    let program = fs::read_to_string(matches.value_of("program").unwrap())
+        .expect("failed reading program file");
+    let mut machine = Machine::new();
+    machine
+        .init(program.trim())
+        .expect("failed parsing program");
+
+    let stdin = BufReader::new(io::stdin());
+    let reader: Box<dyn BufRead + Send> = match matches.value_of("script") {
+        Some(path) => {
+            let script = BufReader::new(File::open(path).expect("failed opening script"));
+            if matches.is_present("batch") {
+                Box::new(script)
+            } else {
+                Box::new(script.chain(stdin))
+            }
+        }
+        None => Box::new(stdin),
+    };
+
+    let process = IntcodeProcess::new(machine, AsciiInput::new(reader), AsciiOutput::stdout());
+    if let Err(err) = task::block_on(process.run()) {
+        eprintln!("\nstopped: {:?}", err);
+        process::exit(1);
+    }
+}
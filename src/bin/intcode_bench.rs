@@ -0,0 +1,99 @@
+use clap::{App, Arg};
+
+use adventofcode_2019::intcode_assembler::assemble;
+use adventofcode_2019::intcode_computer::*;
+use adventofcode_2019::intcode_engine::Engine;
+
+use std::time::{Duration, Instant};
+use std::{fs, str::FromStr};
+
+/// Sums the numbers from its input down to 1, used when no program is given.
+const DEFAULT_PROGRAM: &str = "
+        in [n]
+loop:   add [sum], [n], [sum]
+        add [n], #-1, [n]
+        jt [n], #loop
+        out [sum]
+        hlt
+n:      data 0
+sum:    data 0
+";
+
+fn main() {
+    let matches = App::new("Intcode benchmark")
+        .version("1.0")
+        .about("compares the interpreter with the pre-decoded engine")
+        .arg(
+            Arg::with_name("program")
+                .help("file containing the Intcode program (defaults to a summing loop)")
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("input")
+                .help("comma-separated values to give the program as input")
+                .short("i")
+                .long("input")
+                .value_name("VALUES")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("runs")
+                .help("number of times to run the program")
+                .short("n")
+                .long("runs")
+                .value_name("N")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let program = match matches.value_of("program") {
+        Some(path) => fs::read_to_string(path)
+            .expect("failed reading program file")
+            .trim()
+            .to_string(),
+        None => assemble(DEFAULT_PROGRAM).unwrap().to_string(),
+    };
+    let input = match matches.value_of("input") {
+        Some(values) => IntVec::from_str(values)
+            .expect("failed parsing input")
+            .into_inner(),
+        None if matches.value_of("program").is_none() => vec![100_000],
+        None => Vec::new(),
+    };
+    let runs: u32 = matches
+        .value_of("runs")
+        .map(|n| n.parse().expect("invalid number of runs"))
+        .unwrap_or(100);
+
+    let mut machine = Machine::new();
+    machine.init(&program).expect("failed parsing program");
+    let engine = Engine::from(&machine);
+
+    let (expected, interpreted) = time(runs, || {
+        machine.clone().run_with_input(input.clone()).unwrap()
+    });
+    let (output, compiled) = time(runs, || {
+        engine.clone().run_with_input(input.clone()).unwrap()
+    });
+    assert_eq!(
+        output, expected,
+        "engine output differs from the interpreter"
+    );
+
+    println!("output:      {}", IntVec(expected));
+    println!("interpreter: {:?} per run", interpreted / runs);
+    println!("engine:      {:?} per run", compiled / runs);
+    println!(
+        "speedup:     {:.2}x",
+        interpreted.as_secs_f64() / compiled.as_secs_f64()
+    );
+}
+
+fn time<F: FnMut() -> Vec<Int>>(runs: u32, mut f: F) -> (Vec<Int>, Duration) {
+    let start = Instant::now();
+    let mut output = Vec::new();
+    for _ in 0..runs {
+        output = f();
+    }
+    (output, start.elapsed())
+}
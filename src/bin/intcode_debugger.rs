@@ -0,0 +1,285 @@
+use async_std::{channel::Sender, task};
+use clap::{App, Arg};
+
+use adventofcode_2019::intcode_computer::*;
+use adventofcode_2019::intcode_debugger::*;
+
+use std::io::{self, BufRead, Write};
+use std::{fs, str::FromStr};
+
+const HELP: &str = "\
+s, step [n]          execute n instructions (default 1)
+rs, rstep [n]        undo n instructions (default 1)
+c, continue          run until a breakpoint, watchpoint, input or exit
+b, break [addr]      set a breakpoint, or list breakpoints
+d, delete <addr>     remove a breakpoint
+w, watch [addr]      watch a memory cell, or list watchpoints
+u, unwatch <addr>    stop watching a memory cell
+i, info              show pos, relative base and the next instruction
+rw, rwatch <addr>    rewind to just before the last write to a memory cell
+who <addr>           show which instruction last wrote to a memory cell
+x <addr> [n]         examine n memory cells (default 1)
+l, list [addr] [n]   decode n instructions (default 5) from addr or pos
+set <addr> <value>   write a value to memory
+in <values...>       queue values for the program's input
+h, help              show this message
+q, quit              exit the debugger";
+
+/// Instructions that can be undone without re-executing from a checkpoint.
+const HISTORY_WINDOW: usize = 10_000;
+
+const CHECKPOINT_INTERVAL: u64 = 100_000;
+
+fn main() {
+    let matches = App::new("Intcode debugger")
+        .version("1.0")
+        .arg(
+            Arg::with_name("program")
+                .help("file containing the Intcode program")
+                .required(true)
+                .index(1),
+        )
+        .get_matches();
+
+    let program = fs::read_to_string(matches.value_of("program").unwrap())
+        .expect("failed reading program file");
+
+    let ((in_sender, in_receiver), (out_sender, out_receiver)) = IntcodeComputer::create_io();
+    let mut computer = IntcodeComputer::new(&in_receiver, &out_sender);
+    computer
+        .init(program.trim())
+        .expect("failed parsing program");
+    computer.enable_history(HISTORY_WINDOW, CHECKPOINT_INTERVAL);
+    let mut debugger = Debugger::new(computer);
+
+    task::spawn(async move {
+        while let Ok(output) = out_receiver.recv().await {
+            println!("output: {}", output);
+        }
+    });
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    print_location(&debugger);
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush().unwrap();
+        let line = match lines.next() {
+            Some(line) => line.unwrap(),
+            None => break,
+        };
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+
+        let result = match command {
+            "s" | "step" => arg(&args, 0, Some(1)).and_then(|n| {
+                for _ in 0..n {
+                    let reason = resume(&mut debugger, &in_sender, &mut lines, false)?;
+                    if reason != StopReason::Step {
+                        report(&debugger, &reason);
+                        return Ok(());
+                    }
+                }
+                print_location(&debugger);
+                Ok(())
+            }),
+            "rs" | "rstep" => arg(&args, 0, Some(1)).and_then(|n| {
+                for _ in 0..n {
+                    if !debugger
+                        .step_back()
+                        .map_err(|err| format!("error: {:?}", err))?
+                    {
+                        println!("at the start of history");
+                        break;
+                    }
+                }
+                print_location(&debugger);
+                Ok(())
+            }),
+            "rw" | "rwatch" => arg(&args, 0, None).and_then(|addr| {
+                match debugger
+                    .rewind_to_write(addr)
+                    .map_err(|err| format!("error: {:?}", err))?
+                {
+                    Some(_) => print_location(&debugger),
+                    None => println!("nothing has written to {:04}", addr),
+                }
+                Ok(())
+            }),
+            "who" => arg(&args, 0, None).and_then(|addr| {
+                match debugger
+                    .computer()
+                    .last_write(addr)
+                    .map_err(|err| format!("error: {:?}", err))?
+                {
+                    Some((step, pos)) => match debugger.computer().inst_at(pos) {
+                        Ok(inst) => println!("step {}: {:04}: {}", step, pos, inst),
+                        Err(_) => println!("step {}: {:04}", step, pos),
+                    },
+                    None => println!("nothing has written to {:04}", addr),
+                }
+                Ok(())
+            }),
+            "c" | "continue" => resume(&mut debugger, &in_sender, &mut lines, true)
+                .map(|reason| report(&debugger, &reason)),
+            "b" | "break" if args.is_empty() => {
+                for addr in debugger.breakpoints() {
+                    println!("breakpoint at {:04}", addr);
+                }
+                Ok(())
+            }
+            "b" | "break" => arg(&args, 0, None).map(|addr| {
+                debugger.add_breakpoint(addr);
+            }),
+            "d" | "delete" => arg(&args, 0, None).map(|addr| {
+                if !debugger.remove_breakpoint(addr) {
+                    println!("no breakpoint at {:04}", addr);
+                }
+            }),
+            "w" | "watch" if args.is_empty() => {
+                for addr in debugger.watchpoints() {
+                    println!("watching {:04} = {}", addr, debugger.computer().peek(*addr));
+                }
+                Ok(())
+            }
+            "w" | "watch" => arg(&args, 0, None).map(|addr| {
+                debugger.add_watchpoint(addr);
+            }),
+            "u" | "unwatch" => arg(&args, 0, None).map(|addr| {
+                if !debugger.remove_watchpoint(addr) {
+                    println!("not watching {:04}", addr);
+                }
+            }),
+            "i" | "info" => {
+                let computer = debugger.computer();
+                println!("state:         {:?}", computer.state());
+                println!("pos:           {}", computer.pos());
+                println!("relative base: {}", computer.relative_base());
+                println!("memory size:   {}", computer.mem().len());
+                print_location(&debugger);
+                Ok(())
+            }
+            "x" => arg(&args, 0, None).and_then(|addr| {
+                let count: usize = arg(&args, 1, Some(1))?;
+                for addr in addr..addr + count {
+                    println!("{:04}: {}", addr, debugger.computer().peek(addr));
+                }
+                Ok(())
+            }),
+            "l" | "list" => {
+                let pos = debugger.computer().pos();
+                arg(&args, 0, Some(pos)).and_then(|mut addr| {
+                    let count: usize = arg(&args, 1, Some(5))?;
+                    for _ in 0..count {
+                        match debugger.computer().inst_at(addr) {
+                            Ok(inst) => {
+                                println!("{:04}: {}", addr, inst);
+                                addr += inst.size();
+                            }
+                            Err(_) => {
+                                println!("{:04}: data {}", addr, debugger.computer().peek(addr));
+                                addr += 1;
+                            }
+                        }
+                    }
+                    Ok(())
+                })
+            }
+            "set" => arg(&args, 0, None).and_then(|addr| {
+                let val: Int = arg(&args, 1, None)?;
+                debugger.computer_mut().write(addr, val);
+                Ok(())
+            }),
+            "in" => args
+                .iter()
+                .map(|arg| Int::from_str(arg).map_err(|_| format!("invalid value `{}`", arg)))
+                .collect::<Result<Vec<Int>, String>>()
+                .map(|values| {
+                    for val in values {
+                        task::block_on(in_sender.send(val)).unwrap();
+                    }
+                }),
+            "h" | "help" => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            "q" | "quit" => break,
+            _ => Err(format!("unknown command `{}`, try `help`", command)),
+        };
+
+        if let Err(err) = result {
+            println!("{}", err);
+        }
+    }
+}
+
+/// Steps or continues, asking the user for program input whenever it's
+/// needed.
+fn resume(
+    debugger: &mut Debugger,
+    in_sender: &Sender<Int>,
+    lines: &mut impl Iterator<Item = io::Result<String>>,
+    continuing: bool,
+) -> Result<StopReason, String> {
+    loop {
+        let reason = if continuing {
+            task::block_on(debugger.cont())
+        } else {
+            task::block_on(debugger.step())
+        }
+        .map_err(|err| format!("error: {:?}", err))?;
+
+        if reason != StopReason::AwaitingInput {
+            return Ok(reason);
+        }
+
+        print!("input> ");
+        io::stdout().flush().unwrap();
+        match lines.next() {
+            Some(Ok(line)) => match Int::from_str(line.trim()) {
+                Ok(val) => task::block_on(in_sender.send(val)).unwrap(),
+                Err(_) => println!("invalid value `{}`", line.trim()),
+            },
+            _ => return Ok(reason),
+        }
+    }
+}
+
+fn report(debugger: &Debugger, reason: &StopReason) {
+    match reason {
+        StopReason::Breakpoint(addr) => println!("breakpoint at {:04}", addr),
+        StopReason::Watchpoint { addr, old, new } => {
+            println!("watchpoint {:04}: {} -> {}", addr, old, new)
+        }
+        StopReason::Exited => {
+            println!("program exited");
+            return;
+        }
+        StopReason::AwaitingInput => println!("waiting for input"),
+        StopReason::Step => {}
+    }
+    print_location(debugger);
+}
+
+fn print_location(debugger: &Debugger) {
+    let pos = debugger.computer().pos();
+    match debugger.computer().next_inst() {
+        Ok(inst) => println!("=> {:04}: {}", pos, inst),
+        Err(err) => println!("=> {:04}: {:?}", pos, err),
+    }
+}
+
+fn arg<T: FromStr>(args: &[&str], index: usize, default: Option<T>) -> Result<T, String> {
+    match (args.get(index), default) {
+        (Some(arg), _) => arg
+            .parse()
+            .map_err(|_| format!("invalid argument `{}`", arg)),
+        (None, Some(default)) => Ok(default),
+        (None, None) => Err("missing argument, try `help`".to_string()),
+    }
+}
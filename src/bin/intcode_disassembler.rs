@@ -0,0 +1,10 @@
+use adventofcode_2019::{get_input, intcode_computer::IntVec, intcode_disassembler::disassemble};
+
+use std::str::FromStr;
+
+fn main() {
+    let input = get_input().unwrap();
+    let program = IntVec::from_str(input.trim()).expect("failed parsing program");
+
+    print!("{}", disassemble(&program));
+}
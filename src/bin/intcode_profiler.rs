@@ -0,0 +1,66 @@
+use clap::{App, Arg};
+
+use adventofcode_2019::intcode_computer::*;
+use adventofcode_2019::intcode_disassembler::disassemble;
+
+use std::{fs, str::FromStr};
+
+fn main() {
+    let matches = App::new("Intcode profiler")
+        .version("1.0")
+        .arg(
+            Arg::with_name("program")
+                .help("file containing the Intcode program")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("input")
+                .help("comma-separated values to give the program as input")
+                .short("i")
+                .long("input")
+                .value_name("VALUES")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("json")
+                .help("print the report as JSON")
+                .long("json"),
+        )
+        .arg(
+            Arg::with_name("annotate")
+                .help("print the program's disassembly annotated with the profile")
+                .short("a")
+                .long("annotate"),
+        )
+        .get_matches();
+
+    let program = fs::read_to_string(matches.value_of("program").unwrap())
+        .expect("failed reading program file");
+    let input = match matches.value_of("input") {
+        Some(values) => IntVec::from_str(values)
+            .expect("failed parsing input")
+            .into_inner(),
+        None => Vec::new(),
+    };
+
+    let mut machine = Machine::new();
+    machine
+        .init(program.trim())
+        .expect("failed parsing program");
+    machine.enable_profiling();
+    match machine.run_with_input(input) {
+        Ok(outputs) => eprintln!("output: {}", IntVec(outputs)),
+        Err(err) => eprintln!("stopped early: {:?}", err),
+    }
+
+    let profile = machine.take_profile().unwrap();
+    if matches.is_present("json") {
+        println!("{}", profile.to_json());
+    } else if matches.is_present("annotate") {
+        let program = IntVec::from_str(program.trim()).unwrap();
+        print!("{}", profile.annotate(&disassemble(&program)));
+    } else {
+        print!("{}", profile);
+    }
+}
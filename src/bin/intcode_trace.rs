@@ -0,0 +1,121 @@
+use clap::{App, Arg, SubCommand};
+
+use adventofcode_2019::intcode_computer::*;
+use adventofcode_2019::intcode_trace::*;
+
+use std::time::Duration;
+use std::{fs, process, str::FromStr};
+
+fn main() {
+    let program_arg = Arg::with_name("program")
+        .help("file containing the Intcode program")
+        .required(true)
+        .index(1);
+    let trace_arg = Arg::with_name("trace")
+        .help("trace file")
+        .required(true)
+        .index(2);
+
+    let matches = App::new("Intcode trace recorder")
+        .version("1.0")
+        .subcommand(
+            SubCommand::with_name("record")
+                .about("runs a program, saving everything it reads and writes")
+                .arg(program_arg.clone())
+                .arg(trace_arg.clone())
+                .arg(
+                    Arg::with_name("input")
+                        .help("comma-separated values to give the program as input")
+                        .short("i")
+                        .long("input")
+                        .value_name("VALUES")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("instructions")
+                        .help("also record every executed instruction")
+                        .long("instructions"),
+                )
+                .arg(
+                    Arg::with_name("max-steps")
+                        .help("stop after executing this many instructions")
+                        .long("max-steps")
+                        .value_name("N")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .help("stop after running for this many seconds")
+                        .long("timeout")
+                        .value_name("SECONDS")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("re-runs a program against a trace, checking it behaves the same")
+                .arg(program_arg)
+                .arg(trace_arg),
+        )
+        .get_matches();
+
+    let (command, matches) = match matches.subcommand() {
+        (command, Some(matches)) => (command, matches),
+        _ => {
+            eprintln!("{}", matches.usage());
+            process::exit(2);
+        }
+    };
+
+    let program = fs::read_to_string(matches.value_of("program").unwrap())
+        .expect("failed reading program file");
+    let mut machine = Machine::new();
+    machine
+        .init(program.trim())
+        .expect("failed parsing program");
+    let trace_path = matches.value_of("trace").unwrap();
+
+    if command == "record" {
+        let input = match matches.value_of("input") {
+            Some(values) => IntVec::from_str(values)
+                .expect("failed parsing input")
+                .into_inner(),
+            None => Vec::new(),
+        };
+
+        let limits = Limits {
+            max_steps: matches
+                .value_of("max-steps")
+                .map(|steps| steps.parse().expect("invalid step limit")),
+            timeout: matches
+                .value_of("timeout")
+                .map(|secs| Duration::from_secs_f64(secs.parse().expect("invalid timeout"))),
+        };
+        if limits != Limits::default() {
+            machine.set_limits(limits);
+        }
+
+        machine.enable_tracing(matches.is_present("instructions"));
+        let result = machine.run_with_input(input);
+        let mut trace = machine.take_trace().unwrap();
+        if let Err(err) = result {
+            // Replays stop at the same point rather than expecting a halt.
+            eprintln!("stopped early: {:?}", err);
+            trace.record_stop();
+        }
+        fs::write(trace_path, trace.to_string()).expect("failed writing trace file");
+        println!("output: {}", IntVec(trace.outputs().collect()));
+    } else {
+        let trace =
+            Trace::from_str(&fs::read_to_string(trace_path).expect("failed reading trace file"))
+                .expect("failed parsing trace file");
+
+        match replay(&machine, &trace) {
+            Ok(_) => println!("replay matches ({} events)", trace.events().len()),
+            Err(err) => {
+                println!("replay differs: {:?}", err);
+                process::exit(1);
+            }
+        }
+    }
+}
@@ -16,10 +16,10 @@ impl<T> Grid<T> {
     }
 
     pub fn bounds(&self) -> ((i64, i64), (i64, i64)) {
-        let min_x = self.keys().fold(std::i64::MAX, |acc, c| acc.min(c.0));
-        let max_x = self.keys().fold(std::i64::MIN, |acc, c| acc.max(c.0));
-        let min_y = self.keys().fold(std::i64::MAX, |acc, c| acc.min(c.1));
-        let max_y = self.keys().fold(std::i64::MIN, |acc, c| acc.max(c.1));
+        let min_x = self.keys().fold(i64::MAX, |acc, c| acc.min(c.0));
+        let max_x = self.keys().fold(i64::MIN, |acc, c| acc.max(c.0));
+        let min_y = self.keys().fold(i64::MAX, |acc, c| acc.min(c.1));
+        let max_y = self.keys().fold(i64::MIN, |acc, c| acc.max(c.1));
 
         ((min_x, min_y), (max_x, max_y))
     }
@@ -97,7 +97,7 @@ where
                 };
                 write!(f, "{}", item)?;
             }
-            write!(f, "\n")?;
+            writeln!(f)?;
         }
         Ok(())
     }
@@ -0,0 +1,537 @@
+//! A small assembly language for Intcode, compatible with the listings
+//! produced by the disassembler.
+//!
+//! ```text
+//! ; Counts down from 3, printing each value.
+//!         add #3, #0, [counter]
+//! loop:   out [counter]
+//!         add [counter], #-1, [counter]
+//!         jt [counter], #loop
+//!         hlt
+//! counter:
+//!         data 0
+//! ```
+//!
+//! Operands are written `[x]` for position mode, `#x` for immediate mode and
+//! `rb+x` for relative mode, where `x` is a number, a label, or a sum of
+//! them (`[table+2]`). A line may start with a numeric address like `0012:`,
+//! which is checked against the address the line assembles to. `data`
+//! accepts numbers, labels and double-quoted ASCII strings.
+
+use crate::intcode_computer::{Error, Instruction, Int, IntVec, Parameter, ParameterMode};
+
+use std::collections::HashMap;
+
+/// Opcodes that can be written in assembly.
+const OPCODES: [Int; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(Int),
+    Str(String),
+    Comma,
+    Colon,
+    Hash,
+    Plus,
+    Minus,
+    LeftBracket,
+    RightBracket,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    line: usize,
+    column: usize,
+}
+
+impl Span {
+    fn error(self, message: String) -> Error {
+        Error::AssemblyError {
+            line: self.line,
+            column: self.column,
+            message,
+        }
+    }
+}
+
+/// A sum of numbers and labels, resolved once every label is known.
+#[derive(Debug, Clone)]
+struct Expr(Vec<(Int, Term, Span)>);
+
+#[derive(Debug, Clone)]
+enum Term {
+    Number(Int),
+    Label(String),
+}
+
+impl Expr {
+    fn eval(&self, labels: &HashMap<String, usize>) -> Result<Int, Error> {
+        self.0.iter().try_fold(0 as Int, |acc, (sign, term, span)| {
+            let val = match term {
+                Term::Number(n) => *n,
+                Term::Label(name) => match labels.get(name) {
+                    Some(addr) => *addr as Int,
+                    None => return Err(span.error(format!("undefined label `{}`", name))),
+                },
+            };
+            sign.checked_mul(val)
+                .and_then(|val| acc.checked_add(val))
+                .ok_or_else(|| span.error("value out of range".to_string()))
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Item {
+    Instruction(Int, Vec<(Expr, ParameterMode)>),
+    Data(Vec<Expr>),
+}
+
+fn tokenize(line: &str, line_no: usize) -> Result<Vec<(Token, Span)>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let span = Span {
+            line: line_no,
+            column: i + 1,
+        };
+        let token = match c {
+            ';' => break,
+            c if c.is_whitespace() => continue,
+            ',' => Token::Comma,
+            ':' => Token::Colon,
+            '#' => Token::Hash,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '[' => Token::LeftBracket,
+            ']' => Token::RightBracket,
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => s.push('\n'),
+                            Some((_, c)) => s.push(c),
+                            None => return Err(span.error("unterminated string".to_string())),
+                        },
+                        Some((_, c)) => s.push(c),
+                        None => return Err(span.error("unterminated string".to_string())),
+                    }
+                }
+                Token::Str(s)
+            }
+            c if c.is_ascii_digit() => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, c)) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    end = j + c.len_utf8();
+                    chars.next();
+                }
+                Token::Number(
+                    line[i..end]
+                        .parse()
+                        .map_err(|_| span.error(format!("invalid number `{}`", &line[i..end])))?,
+                )
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || *c == '_') {
+                        break;
+                    }
+                    end = j + c.len_utf8();
+                    chars.next();
+                }
+                Token::Ident(line[i..end].to_string())
+            }
+            c => return Err(span.error(format!("unexpected character `{}`", c))),
+        };
+        tokens.push((token, span));
+    }
+
+    Ok(tokens)
+}
+
+struct LineParser {
+    tokens: Vec<(Token, Span)>,
+    pos: usize,
+    end: Span,
+}
+
+impl LineParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn span(&self) -> Span {
+        self.tokens
+            .get(self.pos)
+            .map(|(_, span)| *span)
+            .unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<(Token, Span)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<(), Error> {
+        let span = self.span();
+        match self.next() {
+            Some((token, _)) if token == expected => Ok(()),
+            _ => Err(span.error(format!("expected {}", what))),
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn term(&mut self, sign: Int) -> Result<(Int, Term, Span), Error> {
+        match self.next() {
+            Some((Token::Number(n), span)) => Ok((sign, Term::Number(n), span)),
+            Some((Token::Ident(name), span)) if name != "rb" => Ok((sign, Term::Label(name), span)),
+            Some((_, span)) => Err(span.error("expected a number or label".to_string())),
+            None => Err(self.end.error("expected a number or label".to_string())),
+        }
+    }
+
+    /// Parses `a + b - c`, where the leading term may also be signed.
+    fn expr(&mut self) -> Result<Expr, Error> {
+        let mut terms = Vec::new();
+
+        let sign = match self.peek() {
+            Some(Token::Minus) => {
+                self.next();
+                -1
+            }
+            Some(Token::Plus) => {
+                self.next();
+                1
+            }
+            _ => 1,
+        };
+        terms.push(self.term(sign)?);
+
+        loop {
+            let sign = match self.peek() {
+                Some(Token::Plus) => 1,
+                Some(Token::Minus) => -1,
+                _ => break,
+            };
+            self.next();
+            terms.push(self.term(sign)?);
+        }
+
+        Ok(Expr(terms))
+    }
+
+    fn operand(&mut self) -> Result<(Expr, ParameterMode), Error> {
+        match self.peek() {
+            Some(Token::Hash) => {
+                self.next();
+                Ok((self.expr()?, ParameterMode::Immediate))
+            }
+            Some(Token::LeftBracket) => {
+                self.next();
+                let expr = self.expr()?;
+                self.expect(Token::RightBracket, "`]`")?;
+                Ok((expr, ParameterMode::Position))
+            }
+            Some(Token::Ident(name)) if name == "rb" => {
+                let span = self.span();
+                self.next();
+                let expr = match self.peek() {
+                    Some(Token::Plus) | Some(Token::Minus) => self.expr()?,
+                    _ => Expr(vec![(1, Term::Number(0), span)]),
+                };
+                Ok((expr, ParameterMode::Relative))
+            }
+            _ => Err(self
+                .span()
+                .error("expected an operand like `[x]`, `#x` or `rb+x`".to_string())),
+        }
+    }
+
+    fn data_value(&mut self, values: &mut Vec<Expr>) -> Result<(), Error> {
+        if let Some(Token::Str(s)) = self.peek() {
+            let span = self.span();
+            for c in s.clone().chars() {
+                if !c.is_ascii() {
+                    return Err(span.error(format!("non-ASCII character `{}` in string", c)));
+                }
+                values.push(Expr(vec![(1, Term::Number(c as Int), span)]));
+            }
+            self.next();
+            Ok(())
+        } else {
+            values.push(self.expr()?);
+            Ok(())
+        }
+    }
+
+    fn comma_separated<T, F>(&mut self, mut f: F) -> Result<Vec<T>, Error>
+    where
+        F: FnMut(&mut Self, &mut Vec<T>) -> Result<(), Error>,
+    {
+        let mut items = Vec::new();
+        if self.at_end() {
+            return Ok(items);
+        }
+
+        loop {
+            f(self, &mut items)?;
+            if self.at_end() {
+                return Ok(items);
+            }
+            self.expect(Token::Comma, "`,`")?;
+        }
+    }
+}
+
+/// Looks up a mnemonic, returning its opcode and number of parameters.
+fn lookup(mnemonic: &str) -> Option<(Int, usize)> {
+    OPCODES.iter().find_map(|opcode| {
+        let inst = Instruction::parse({
+            let mut first = true;
+            move || {
+                if first {
+                    first = false;
+                    *opcode
+                } else {
+                    0
+                }
+            }
+        })
+        .ok()?;
+
+        if inst.mnemonic() == mnemonic {
+            Some((*opcode, inst.size() - 1))
+        } else {
+            None
+        }
+    })
+}
+
+fn parse_line(
+    tokens: Vec<(Token, Span)>,
+    end: Span,
+    addr: usize,
+    labels: &mut HashMap<String, usize>,
+) -> Result<Option<Item>, Error> {
+    let mut parser = LineParser {
+        tokens,
+        pos: 0,
+        end,
+    };
+
+    // Labels and address annotations
+    while let Some((token, Some(Token::Colon))) = parser.tokens.get(parser.pos).map(|(token, _)| {
+        (
+            token.clone(),
+            parser.tokens.get(parser.pos + 1).map(|t| &t.0),
+        )
+    }) {
+        let span = parser.span();
+        match token {
+            Token::Number(n) if n as usize != addr => {
+                return Err(span.error(format!(
+                    "address {} does not match the current address {}",
+                    n, addr
+                )));
+            }
+            Token::Number(_) => {}
+            Token::Ident(name) if name == "rb" => {
+                return Err(span.error("`rb` is reserved and cannot be a label".to_string()));
+            }
+            Token::Ident(name) => {
+                if labels.insert(name.clone(), addr).is_some() {
+                    return Err(span.error(format!("duplicate label `{}`", name)));
+                }
+            }
+            _ => return Err(span.error("expected a label".to_string())),
+        }
+        parser.pos += 2;
+    }
+
+    let (mnemonic, span) = match parser.next() {
+        Some((Token::Ident(mnemonic), span)) => (mnemonic, span),
+        Some((_, span)) => return Err(span.error("expected a mnemonic".to_string())),
+        None => return Ok(None),
+    };
+
+    if mnemonic == "data" {
+        let values = parser.comma_separated(|p, values| p.data_value(values))?;
+        return Ok(Some(Item::Data(values)));
+    }
+
+    let (opcode, arity) = match lookup(&mnemonic) {
+        Some(found) => found,
+        None => return Err(span.error(format!("unknown mnemonic `{}`", mnemonic))),
+    };
+    let operands = parser.comma_separated(|p, operands| {
+        operands.push(p.operand()?);
+        Ok(())
+    })?;
+    if operands.len() != arity {
+        return Err(span.error(format!(
+            "`{}` takes {} operands, found {}",
+            mnemonic,
+            arity,
+            operands.len()
+        )));
+    }
+
+    Ok(Some(Item::Instruction(opcode, operands)))
+}
+
+/// Assembles source text into an Intcode program.
+pub fn assemble(source: &str) -> Result<IntVec, Error> {
+    let mut labels = HashMap::new();
+    let mut items = Vec::new();
+    let mut addr = 0;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let end = Span {
+            line: line_no,
+            column: line.len() + 1,
+        };
+        let tokens = tokenize(line, line_no)?;
+        if let Some(item) = parse_line(tokens, end, addr, &mut labels)? {
+            addr += match &item {
+                Item::Instruction(_, operands) => operands.len() + 1,
+                Item::Data(values) => values.len(),
+            };
+            items.push(item);
+        }
+    }
+
+    let mut program = Vec::with_capacity(addr);
+    for item in items {
+        match item {
+            Item::Instruction(opcode, operands) => {
+                let params = operands
+                    .into_iter()
+                    .map(|(expr, mode)| Ok(Parameter(expr.eval(&labels)?, mode)))
+                    .collect::<Result<Vec<Parameter>, Error>>()?;
+                let modes = params.iter().enumerate().fold(0, |acc, (i, p)| {
+                    acc + p.1.to_int() * (10 as Int).pow(i as u32)
+                });
+                program.push(modes * 100 + opcode);
+                program.extend(params.iter().map(|p| p.0));
+            }
+            Item::Data(values) => {
+                for value in values {
+                    program.push(value.eval(&labels)?);
+                }
+            }
+        }
+    }
+
+    Ok(IntVec(program))
+}
+
+#[allow(dead_code)]
+fn assembly_error(source: &str) -> (usize, usize, String) {
+    match assemble(source) {
+        Err(Error::AssemblyError {
+            line,
+            column,
+            message,
+        }) => (line, column, message),
+        other => panic!("expected an assembly error, got {:?}", other),
+    }
+}
+
+#[test]
+fn assembles_instructions() {
+    let program = assemble(
+        "
+        ; Day 5's first example
+        mul [4], #3, [4]
+        data 33
+        ",
+    )
+    .unwrap();
+
+    assert_eq!(program.into_inner(), vec![1002, 4, 3, 4, 33]);
+}
+
+#[test]
+fn resolves_labels_and_relative_operands() {
+    let program = assemble(
+        "
+        start:  rbo #1
+                out rb-1
+                add [counter], #1, [counter]
+                eq [counter], #16, [done]
+                jf [done], #start
+                hlt
+        counter: data 0
+        done:    data 0, \"ok\\n\"
+        ",
+    )
+    .unwrap();
+
+    assert_eq!(
+        program.into_inner(),
+        vec![
+            109, 1, 204, -1, 1001, 16, 1, 16, 1008, 16, 16, 17, 1006, 17, 0, 99, 0, 0, 111, 107, 10
+        ]
+    );
+}
+
+#[test]
+fn round_trips_with_the_disassembler() {
+    use crate::intcode_disassembler::disassemble;
+    use std::str::FromStr;
+
+    for source in [
+        "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+        "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10",
+    ] {
+        let program = IntVec::from_str(source).unwrap();
+        let listing = disassemble(&program).to_string();
+        assert_eq!(assemble(&listing).unwrap().into_inner(), program.into_inner());
+    }
+}
+
+#[test]
+fn reports_error_positions() {
+    assert_eq!(
+        assembly_error("hlt\n  frob [1]"),
+        (2, 3, "unknown mnemonic `frob`".to_string())
+    );
+    assert_eq!(
+        assembly_error("add [1], #2"),
+        (1, 1, "`add` takes 3 operands, found 2".to_string())
+    );
+    assert_eq!(
+        assembly_error("out [nowhere]"),
+        (1, 6, "undefined label `nowhere`".to_string())
+    );
+    assert_eq!(assembly_error("out [1"), (1, 7, "expected `]`".to_string()));
+    assert_eq!(
+        assembly_error("hlt\n0002: hlt"),
+        (
+            2,
+            1,
+            "address 2 does not match the current address 1".to_string()
+        )
+    );
+    assert_eq!(
+        assembly_error("a: hlt\na: hlt"),
+        (2, 1, "duplicate label `a`".to_string())
+    );
+    assert_eq!(
+        assembly_error("out #9223372036854775807 + 1"),
+        (1, 28, "value out of range".to_string())
+    );
+}
@@ -1,27 +1,155 @@
-use async_std::channel::{bounded, Receiver, RecvError, Sender};
+use async_std::{
+    channel::{bounded, Receiver, Sender},
+    future,
+    task::{self, JoinHandle},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::intcode_history::History;
+use crate::intcode_io::{IntcodeInput, IntcodeOutput};
+use crate::intcode_opcodes::{Opcodes, Operands};
+use crate::intcode_profiler::Profile;
+use crate::intcode_trace::Trace;
 
 use std::{
+    collections::VecDeque,
     fmt,
     ops::{Deref, DerefMut},
     str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 pub const BUFFER_SIZE: usize = 50;
 
+/// How many instructions run between checks of the clock when a machine
+/// has a timeout, since reading it on every step is comparatively slow.
+const CLOCK_INTERVAL: u64 = 1024;
+
 pub type Int = i64;
 
-#[derive(Debug)]
-pub struct IntcodeComputer<'a> {
+/// The state of an Intcode program and the instruction semantics, without
+/// any I/O attached. Input is queued with `push_input` and output is handed
+/// back from `step`/`resume`, so it can be driven from plain loops.
+#[derive(Debug, Clone, Default)]
+pub struct Machine {
     state: OperationState,
     mem: Vec<Int>,
     pos: usize,
     relative_base: isize,
+    inputs: VecDeque<Int>,
+    profile: Option<Profile>,
+    trace: Option<Trace>,
+    history: Option<History>,
+    strict: Option<StrictMode>,
+    budget: Option<Budget>,
+    opcodes: Option<Arc<Opcodes>>,
+    /// An address being watched for writes, and whether it's been written
+    /// since the flag was last cleared.
+    watch: Option<(usize, bool)>,
+}
+
+/// Rules a strict `Machine` checks each instruction against before
+/// executing it. A lenient machine instead wraps negative addresses around,
+/// reads unknown parameter modes as position mode and grows memory as far
+/// as the program asks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrictMode {
+    /// Number of memory cells the program may use.
+    pub max_memory: usize,
+}
+
+/// Why a strict machine refused to execute an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    NegativeAddress(isize),
+    /// An address at or past the memory limit.
+    OutOfMemory(usize),
+    /// A parameter mode digit that isn't 0, 1 or 2, or any mode digit for
+    /// a parameter the instruction doesn't have.
+    InvalidParameterMode(Int),
+    /// A write parameter in immediate mode.
+    ImmediateWrite,
+    /// A jump to before the start or past the end of memory.
+    JumpOutOfMemory(Int),
+}
+
+/// Bounds on how long a machine may run before it stops with
+/// `Error::LimitExceeded`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Most instructions to execute.
+    pub max_steps: Option<u64>,
+    /// Most time to spend, including time spent waiting for input. A bare
+    /// `Machine` only notices once the next instruction is due to run, but
+    /// `IntcodeComputer` and `IntcodeProcess` also stop waiting on their
+    /// input when it runs out.
+    pub timeout: Option<Duration>,
+}
+
+/// Which limit a machine ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps(u64),
+    Time(Duration),
+}
+
+/// What's been used of a machine's limits so far.
+#[derive(Debug, Clone)]
+struct Budget {
+    limits: Limits,
+    steps: u64,
+    started: Instant,
+}
+
+impl Budget {
+    fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            steps: 0,
+            started: Instant::now(),
+        }
+    }
+
+    fn exceeded(&self) -> Option<Limit> {
+        match self.limits {
+            Limits {
+                max_steps: Some(max),
+                ..
+            } if self.steps >= max => Some(Limit::Steps(max)),
+            Limits {
+                timeout: Some(timeout),
+                ..
+            } if self.steps.is_multiple_of(CLOCK_INTERVAL) && self.started.elapsed() >= timeout => {
+                Some(Limit::Time(timeout))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What a machine did, or why it stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// An instruction was executed and the machine can keep going.
+    Running,
+    /// The next instruction is an input and no input is queued.
+    AwaitingInput,
+    Output(Int),
+    Halted,
+}
+
+/// A `Machine` wired up to async channels for its input and output.
+#[derive(Debug)]
+pub struct IntcodeComputer<'a> {
+    machine: Machine,
     input: &'a Receiver<Int>,
     output: &'a Sender<Int>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum OperationState {
+    #[default]
     Preinit,
     Ready,
     Running,
@@ -79,126 +207,624 @@ impl FromStr for IntVec {
     }
 }
 
-pub type IOChannels = (Sender<Int>, Receiver<Int>);
+/// Everything needed to pick a machine back up where it left off.
+///
+/// Its `Display` output is a small line-based text format that `FromStr`
+/// reads back, for saving snapshots to disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub state: OperationState,
+    pub mem: Vec<Int>,
+    pub pos: usize,
+    pub relative_base: isize,
+    pub inputs: Vec<Int>,
+}
 
-impl<'a> IntcodeComputer<'a> {
-    pub fn new(input: &'a Receiver<Int>, output: &'a Sender<Int>) -> Self {
-        Self {
-            state: OperationState::Preinit,
-            mem: Vec::new(),
-            pos: 0,
-            relative_base: 0,
-            input,
-            output,
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "state: {:?}", self.state)?;
+        writeln!(f, "pos: {}", self.pos)?;
+        writeln!(f, "relative_base: {}", self.relative_base)?;
+        writeln!(f, "inputs: {}", IntVec(self.inputs.clone()))?;
+        writeln!(f, "mem: {}", IntVec(self.mem.clone()))
+    }
+}
+
+impl FromStr for Snapshot {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.lines().filter_map(|line| line.split_once(": "));
+        let mut field = |name: &str| -> Result<&str, Error> {
+            match fields.next() {
+                Some((key, val)) if key == name => Ok(val.trim()),
+                _ => Err(Error::SnapshotParseError(format!("expected `{}`", name))),
+            }
+        };
+        fn number<T: FromStr>(name: &str, val: &str) -> Result<T, Error> {
+            val.parse()
+                .map_err(|_| Error::SnapshotParseError(format!("invalid {} `{}`", name, val)))
         }
+        let ints = |val: &str| -> Result<Vec<Int>, Error> {
+            if val.is_empty() {
+                Ok(Vec::new())
+            } else {
+                Ok(IntVec::from_str(val)?.into_inner())
+            }
+        };
+
+        let state = match field("state")? {
+            "Preinit" => OperationState::Preinit,
+            "Ready" => OperationState::Ready,
+            "Running" => OperationState::Running,
+            "Exited" => OperationState::Exited,
+            other => {
+                return Err(Error::SnapshotParseError(format!(
+                    "invalid state `{}`",
+                    other
+                )))
+            }
+        };
+
+        Ok(Snapshot {
+            state,
+            pos: number("pos", field("pos")?)?,
+            relative_base: number("relative_base", field("relative_base")?)?,
+            inputs: ints(field("inputs")?)?,
+            mem: ints(field("mem")?)?,
+        })
     }
+}
 
-    pub fn create_io() -> (IOChannels, IOChannels) {
-        (bounded(BUFFER_SIZE), bounded(BUFFER_SIZE))
+impl From<Snapshot> for Machine {
+    fn from(snapshot: Snapshot) -> Self {
+        let mut machine = Machine::new();
+        machine.restore(&snapshot);
+        machine
+    }
+}
+
+pub type IOChannels = (Sender<Int>, Receiver<Int>);
+
+impl Machine {
+    pub fn new() -> Self {
+        Self::default()
     }
 
     pub fn state(&self) -> &OperationState {
         &self.state
     }
 
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn relative_base(&self) -> isize {
+        self.relative_base
+    }
+
+    pub fn mem(&self) -> &[Int] {
+        &self.mem
+    }
+
     pub fn init(&mut self, program: &str) -> Result<(), Error> {
         self.state = OperationState::Ready;
         self.mem = IntVec::from_str(program)?.into_inner();
         self.pos = 0;
         self.relative_base = 0;
+        self.inputs.clear();
+        self.reset_history();
         Ok(())
     }
 
-    async fn get_input(&self) -> Result<Int, RecvError> {
-        self.input.recv().await
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            state: self.state,
+            mem: self.mem.clone(),
+            pos: self.pos,
+            relative_base: self.relative_base,
+            inputs: self.inputs.iter().copied().collect(),
+        }
     }
 
-    pub async fn repeat(&mut self) -> Result<i64, Error> {
-        let start_mem = self.mem.clone();
+    /// Puts the machine back into the state captured by `snapshot`.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.load(snapshot);
+        self.reset_history();
+    }
 
-        loop {
-            self.run().await?;
-            self.state = OperationState::Ready;
-            self.pos = 0;
-            self.relative_base = 0;
-            self.mem = start_mem.clone();
+    fn load(&mut self, snapshot: &Snapshot) {
+        self.state = snapshot.state;
+        self.mem = snapshot.mem.clone();
+        self.pos = snapshot.pos;
+        self.relative_base = snapshot.relative_base;
+        self.inputs = snapshot.inputs.iter().copied().collect();
+    }
+
+    /// Makes the machine fail with `Error::Fault` instead of executing
+    /// instructions that break the rules in `strict`. Carries on across
+    /// `init` and `restore`.
+    pub fn enable_strict(&mut self, strict: StrictMode) {
+        self.strict = Some(strict);
+    }
+
+    pub fn strict(&self) -> Option<StrictMode> {
+        self.strict
+    }
+
+    pub fn disable_strict(&mut self) {
+        self.strict = None;
+    }
+
+    /// Teaches the machine extra opcodes. They carry on across `init` and
+    /// `restore`, and are shared with any clones.
+    pub fn set_opcodes(&mut self, opcodes: Opcodes) {
+        self.opcodes = Some(Arc::new(opcodes));
+    }
+
+    pub fn opcodes(&self) -> Option<&Opcodes> {
+        self.opcodes.as_deref()
+    }
+
+    /// Stops the machine with `Error::LimitExceeded` once it goes past
+    /// `limits`, counting from now. The machine is left as it was before
+    /// the instruction that would have gone over, so it can carry on after
+    /// setting new limits.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.budget = Some(Budget::new(limits));
+    }
+
+    pub fn limits(&self) -> Option<Limits> {
+        self.budget.as_ref().map(|budget| budget.limits)
+    }
+
+    /// Instructions executed since the limits were set.
+    pub fn steps_used(&self) -> Option<u64> {
+        self.budget.as_ref().map(|budget| budget.steps)
+    }
+
+    /// How long until the timeout, if there is one.
+    pub fn time_left(&self) -> Option<Duration> {
+        let budget = self.budget.as_ref()?;
+        let timeout = budget.limits.timeout?;
+        Some(timeout.saturating_sub(budget.started.elapsed()))
+    }
+
+    pub fn clear_limits(&mut self) {
+        self.budget = None;
+    }
+
+    /// Starts counting executions and memory accesses. Profiling carries
+    /// on across `init` and `restore`.
+    pub fn enable_profiling(&mut self) {
+        self.profile.get_or_insert_with(Profile::default);
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Stops profiling, handing back what was collected.
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    /// Starts recording a trace of everything the program consumes and
+    /// produces, and every instruction it executes if `instructions` is set.
+    pub fn enable_tracing(&mut self, instructions: bool) {
+        self.trace = Some(Trace::new(instructions));
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Stops tracing, handing back what was recorded.
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    /// Starts keeping a reversible history, with undo records for the last
+    /// `window` instructions and a checkpoint every `interval` instructions
+    /// to rewind further than that.
+    pub fn enable_history(&mut self, window: usize, interval: u64) {
+        self.history = Some(History::new(window, interval, self.snapshot()));
+    }
+
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+
+    /// Starts the history over from the current state, since the old one no
+    /// longer leads here.
+    fn reset_history(&mut self) {
+        if let Some(history) = self.history.take() {
+            self.enable_history(history.window(), history.interval());
         }
     }
 
-    pub async fn run(&mut self) -> Result<(), Error> {
-        use Instruction::*;
+    /// Undoes the last instruction, returning false if there's nothing left
+    /// to undo.
+    pub fn step_back(&mut self) -> Result<bool, Error> {
+        let history = self.history_ref()?;
+        match history.step() {
+            step if step == history.oldest_checkpoint() => Ok(false),
+            step => self.rewind_to(step - 1).map(|_| true),
+        }
+    }
 
-        loop {
-            let inst = self.get_inst()?;
-            if cfg!(feature = "debug") {
-                dbg!(&self.relative_base, &inst);
-            }
-            match inst {
-                Add(lhs, rhs, dest) => {
-                    let lhs = self.get(&lhs);
-                    let rhs = self.get(&rhs);
-                    let dest = self.get_addr(&dest);
-                    self.write(dest, lhs + rhs);
+    /// Puts the machine back the way it was after `step` instructions.
+    /// Inputs consumed since then are queued again, ahead of any still
+    /// waiting. Output can't be taken back, and neither can anything before
+    /// the history's oldest checkpoint.
+    pub fn rewind_to(&mut self, step: u64) -> Result<(), Error> {
+        let history = self.history_ref()?;
+        if step >= history.step() {
+            return Ok(());
+        }
+        if step < history.oldest_checkpoint() {
+            return Err(Error::HistoryError(format!(
+                "step {} is from before the oldest checkpoint",
+                step
+            )));
+        }
+
+        if step >= history.oldest_record() {
+            while let Some(history) = self.history.as_mut().filter(|h| h.step() > step) {
+                let record = history.pop().unwrap();
+                for (addr, old) in record.writes.iter().rev() {
+                    self.mem[*addr] = *old;
                 }
-                Multiply(lhs, rhs, dest) => {
-                    let lhs = self.get(&lhs);
-                    let rhs = self.get(&rhs);
-                    let dest = self.get_addr(&dest);
-                    self.write(dest, lhs * rhs);
+                self.mem.truncate(record.mem_len);
+                self.pos = record.pos;
+                self.relative_base = record.relative_base;
+                self.state = record.state;
+                for input in record.inputs.iter().rev() {
+                    self.inputs.push_front(*input);
                 }
-                Input(dest) => {
-                    let input = self
-                        .get_input()
-                        .await
-                        .map_err(|err| Error::InvalidInputError(err.to_string()))?;
-                    let dest = self.get_addr(&dest);
-                    self.write(dest, input);
+            }
+            return Ok(());
+        }
+
+        let history = self.history.as_mut().unwrap();
+        let (checkpoint, snapshot) = history.checkpoint_before(step);
+        let snapshot = snapshot.clone();
+        let replay_inputs = history.inputs_between(checkpoint, step);
+        let mut requeue = history.inputs_between(step, history.step());
+        requeue.extend(self.inputs.iter().copied());
+        history.truncate(checkpoint);
+
+        // Re-executing shouldn't count towards a profile, trace or limits.
+        let profile = self.profile.take();
+        let trace = self.trace.take();
+        let budget = self.budget.take();
+        self.load(&snapshot);
+        self.inputs = replay_inputs.into();
+        let result = self.replay_until(step);
+        self.inputs = requeue.into();
+        self.profile = profile;
+        self.trace = trace;
+        self.budget = budget;
+        result
+    }
+
+    fn replay_until(&mut self, step: u64) -> Result<(), Error> {
+        while self.history_ref()?.step() < step {
+            if let Status::AwaitingInput = self.step()? {
+                return Err(Error::HistoryError(
+                    "ran out of recorded input while replaying".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the last instruction that wrote to `addr`, returning the step
+    /// it ran at and where it is in memory. Writes from before the oldest
+    /// checkpoint may not be found.
+    pub fn last_write(&self, addr: usize) -> Result<Option<(u64, usize)>, Error> {
+        let history = self.history_ref()?;
+        let found = history
+            .records()
+            .rev()
+            .find(|(_, record)| record.writes.iter().any(|(a, _)| *a == addr));
+        if let Some((step, record)) = found {
+            return Ok(Some((step, record.pos)));
+        }
+
+        // Search the steps that have no undo records by replaying them, one
+        // checkpoint at a time working backwards, so only the latest write
+        // to `addr` is ever kept.
+        let mut machine = Machine {
+            strict: self.strict,
+            opcodes: self.opcodes.clone(),
+            watch: Some((addr, false)),
+            ..Machine::default()
+        };
+        let mut end = history.oldest_record();
+        while end > history.oldest_checkpoint() {
+            let (checkpoint, snapshot) = history.checkpoint_before(end - 1);
+            machine.load(snapshot);
+            machine.inputs = history.inputs_between(checkpoint, end).into();
+
+            let mut found = None;
+            for step in checkpoint..end {
+                let pos = machine.pos;
+                machine.watch = Some((addr, false));
+                if let Status::AwaitingInput = machine.step()? {
+                    return Err(Error::HistoryError(
+                        "ran out of recorded input while replaying".to_string(),
+                    ));
                 }
-                Output(src) => {
-                    self.output
-                        .send(self.get(&src))
-                        .await
-                        .map_err(|err| Error::InvalidOutputError(err.to_string()))?;
+                if let Some((_, true)) = machine.watch {
+                    found = Some((step, pos));
                 }
-                JumpIfTrue(x, dest) => {
-                    if self.get(&x) != 0 {
-                        self.pos = self.get(&dest) as usize;
-                    }
+            }
+            if found.is_some() {
+                return Ok(found);
+            }
+            end = checkpoint;
+        }
+        Ok(None)
+    }
+
+    fn history_ref(&self) -> Result<&History, Error> {
+        self.history
+            .as_ref()
+            .ok_or_else(|| Error::HistoryError("history isn't enabled".to_string()))
+    }
+
+    /// Queues a value for the program's input instructions.
+    pub fn push_input(&mut self, val: Int) {
+        self.inputs.push_back(val);
+    }
+
+    /// Whether a value is waiting to be consumed by the next input instruction.
+    pub fn has_input(&self) -> bool {
+        !self.inputs.is_empty()
+    }
+
+    /// Runs until the program produces output, needs input that hasn't been
+    /// queued, or halts. Never returns `Status::Running`.
+    pub fn resume(&mut self) -> Result<Status, Error> {
+        loop {
+            match self.step()? {
+                Status::Running => {}
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Runs the program to completion with the given input, collecting
+    /// everything it outputs.
+    pub fn run_with_input<I>(&mut self, input: I) -> Result<Vec<Int>, Error>
+    where
+        I: IntoIterator<Item = Int>,
+    {
+        self.inputs.extend(input);
+
+        let mut outputs = Vec::new();
+        loop {
+            match self.resume()? {
+                Status::Output(val) => outputs.push(val),
+                Status::Halted => return Ok(outputs),
+                _ => {
+                    return Err(Error::InvalidInputError(
+                        "program needs more input".to_string(),
+                    ))
                 }
-                JumpIfFalse(x, dest) => {
-                    if self.get(&x) == 0 {
-                        self.pos = self.get(&dest) as usize;
-                    }
+            }
+        }
+    }
+
+    /// Executes the single instruction at `pos`. If it's an input
+    /// instruction and nothing is queued, `pos` is left pointing at it.
+    pub fn step(&mut self) -> Result<Status, Error> {
+        if self.state == OperationState::Exited {
+            return Ok(Status::Halted);
+        }
+
+        let start = self.pos;
+        if let Some(limit) = self.budget.as_ref().and_then(Budget::exceeded) {
+            return Err(Error::LimitExceeded { pos: start, limit });
+        }
+        let (relative_base, state, mem_len) = (self.relative_base, self.state, self.mem.len());
+        if let Some(strict) = self.strict {
+            let inst = self.inst_at(start)?;
+            if let Err(fault) = self.check(start, &inst, strict) {
+                return Err(Error::Fault {
+                    pos: start,
+                    inst,
+                    fault,
+                });
+            }
+        }
+        let inst = self.get_inst()?;
+        if cfg!(feature = "debug") {
+            dbg!(&self.relative_base, &inst);
+        }
+        let mnemonic = inst.mnemonic();
+
+        let status = self.execute(start, inst)?;
+        if status != Status::AwaitingInput {
+            if let Some(budget) = &mut self.budget {
+                budget.steps += 1;
+            }
+            if let Some(profile) = &mut self.profile {
+                profile.record_execution(start, mnemonic, self.mem.len());
+            }
+            if let Some(trace) = &mut self.trace {
+                trace.record_step(start, self.pos, self.relative_base);
+            }
+            if let Some(history) = &mut self.history {
+                if history.push(start, relative_base, state, mem_len) {
+                    let snapshot = self.snapshot();
+                    self.history.as_mut().unwrap().add_checkpoint(snapshot);
+                }
+            }
+        }
+
+        Ok(status)
+    }
+
+    fn execute(&mut self, start: usize, inst: Instruction) -> Result<Status, Error> {
+        use Instruction::*;
+
+        match inst {
+            Add(lhs, rhs, dest) => {
+                let lhs = self.get(&lhs);
+                let rhs = self.get(&rhs);
+                let dest = self.get_addr(&dest);
+                self.store(dest, lhs + rhs);
+            }
+            Multiply(lhs, rhs, dest) => {
+                let lhs = self.get(&lhs);
+                let rhs = self.get(&rhs);
+                let dest = self.get_addr(&dest);
+                self.store(dest, lhs * rhs);
+            }
+            Input(dest) => match self.take_input() {
+                Some(input) => self.store_param(&dest, input),
+                None => {
+                    self.pos = start;
+                    return Ok(Status::AwaitingInput);
                 }
-                LessThan(lhs, rhs, dest) => {
-                    let val = if self.get(&lhs) < self.get(&rhs) {
-                        1
-                    } else {
-                        0
-                    };
-                    let dest = self.get_addr(&dest);
-                    self.write(dest, val);
+            },
+            Output(src) => {
+                let val = self.get(&src);
+                if let Some(trace) = &mut self.trace {
+                    trace.record_output(val);
                 }
-                Equals(lhs, rhs, dest) => {
-                    let val = if self.get(&lhs) == self.get(&rhs) {
-                        1
-                    } else {
-                        0
-                    };
-                    let dest = self.get_addr(&dest);
-                    self.write(dest, val);
+                return Ok(Status::Output(val));
+            }
+            JumpIfTrue(x, dest) => {
+                if self.get(&x) != 0 {
+                    self.pos = self.get(&dest) as usize;
                 }
-                RelativeBase(adj) => {
-                    self.relative_base += self.get(&adj) as isize;
+            }
+            JumpIfFalse(x, dest) => {
+                if self.get(&x) == 0 {
+                    self.pos = self.get(&dest) as usize;
                 }
-                Exit => {
-                    self.exit();
-                    break;
+            }
+            LessThan(lhs, rhs, dest) => {
+                let val = if self.get(&lhs) < self.get(&rhs) {
+                    1
+                } else {
+                    0
+                };
+                let dest = self.get_addr(&dest);
+                self.store(dest, val);
+            }
+            Equals(lhs, rhs, dest) => {
+                let val = if self.get(&lhs) == self.get(&rhs) {
+                    1
+                } else {
+                    0
+                };
+                let dest = self.get_addr(&dest);
+                self.store(dest, val);
+            }
+            RelativeBase(adj) => {
+                self.relative_base += self.get(&adj) as isize;
+            }
+            Exit => {
+                self.exit();
+                return Ok(Status::Halted);
+            }
+            Custom { opcode, params, .. } => {
+                let op = self.opcodes.as_ref().and_then(|ops| ops.get(opcode));
+                let handler = op.expect("decoded an unregistered opcode").handler.clone();
+                let status = handler(&mut Operands::new(self, &params))?;
+                match status {
+                    Status::AwaitingInput => self.pos = start,
+                    Status::Halted => self.exit(),
+                    Status::Output(val) => {
+                        if let Some(trace) = &mut self.trace {
+                            trace.record_output(val);
+                        }
+                    }
+                    Status::Running => {}
                 }
+                return Ok(status);
             }
         }
 
-        Ok(())
+        Ok(Status::Running)
+    }
+
+    /// Checks the instruction at `start` against the strict rules, without
+    /// executing it.
+    fn check(&self, start: usize, inst: &Instruction, strict: StrictMode) -> Result<(), Fault> {
+        use Instruction::*;
+        use ParameterMode::*;
+
+        let in_memory = |addr: isize| {
+            if addr < 0 {
+                Err(Fault::NegativeAddress(addr))
+            } else if addr as usize >= strict.max_memory {
+                Err(Fault::OutOfMemory(addr as usize))
+            } else {
+                Ok(())
+            }
+        };
+        in_memory((start + inst.size() - 1) as isize)?;
+
+        let params = inst.params();
+        let mut modes = self.peek(start) / 100;
+        for i in 0.. {
+            if modes == 0 {
+                break;
+            }
+            let mode = modes % 10;
+            if mode > 2 || (mode != 0 && i >= params.len()) {
+                return Err(Fault::InvalidParameterMode(mode));
+            }
+            modes /= 10;
+        }
+
+        for param in params.iter() {
+            match param.1 {
+                Position => in_memory(param.0 as isize)?,
+                Relative => in_memory(self.relative_base + param.0 as isize)?,
+                Immediate => {}
+            }
+        }
+
+        match inst {
+            Add(.., dest)
+            | Multiply(.., dest)
+            | LessThan(.., dest)
+            | Equals(.., dest)
+            | Input(dest)
+                if dest.1 == Immediate =>
+            {
+                Err(Fault::ImmediateWrite)
+            }
+            JumpIfTrue(x, dest) | JumpIfFalse(x, dest) => {
+                let jumps = (self.value(x) != 0) == matches!(inst, JumpIfTrue(..));
+                let target = self.value(dest);
+                if jumps && (target < 0 || target as usize >= self.mem.len()) {
+                    Err(Fault::JumpOutOfMemory(target))
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The value of a parameter whose address has already been checked.
+    fn value(&self, param: &Parameter) -> Int {
+        match param.1 {
+            ParameterMode::Immediate => param.0,
+            ParameterMode::Position => self.peek(param.0 as usize),
+            ParameterMode::Relative => self.peek((self.relative_base + param.0 as isize) as usize),
+        }
     }
 
     fn exit(&mut self) {
@@ -214,56 +840,56 @@ impl<'a> IntcodeComputer<'a> {
             .join(",")
     }
 
-    fn get_inst(&mut self) -> Result<Instruction, Error> {
-        use Instruction::*;
+    /// Reads memory without growing it, treating unallocated cells as 0.
+    pub fn peek(&self, pos: usize) -> Int {
+        self.mem.get(pos).copied().unwrap_or(0)
+    }
 
-        let val = self.read_next();
-        let opcode = val % 100;
+    /// Decodes the instruction at `pos` without executing it.
+    pub fn next_inst(&self) -> Result<Instruction, Error> {
+        self.inst_at(self.pos)
+    }
 
-        match opcode {
-            1 => Ok(Add(
-                Parameter(self.read_next(), ParameterMode::new(val, 0)?),
-                Parameter(self.read_next(), ParameterMode::new(val, 1)?),
-                Parameter(self.read_next(), ParameterMode::new(val, 2)?),
-            )),
-            2 => Ok(Multiply(
-                Parameter(self.read_next(), ParameterMode::new(val, 0)?),
-                Parameter(self.read_next(), ParameterMode::new(val, 1)?),
-                Parameter(self.read_next(), ParameterMode::new(val, 2)?),
-            )),
-            3 => Ok(Input(Parameter(
-                self.read_next(),
-                ParameterMode::new(val, 0)?,
-            ))),
-            4 => Ok(Output(Parameter(
-                self.read_next(),
-                ParameterMode::new(val, 0)?,
-            ))),
-            5 => Ok(JumpIfTrue(
-                Parameter(self.read_next(), ParameterMode::new(val, 0)?),
-                Parameter(self.read_next(), ParameterMode::new(val, 1)?),
-            )),
-            6 => Ok(JumpIfFalse(
-                Parameter(self.read_next(), ParameterMode::new(val, 0)?),
-                Parameter(self.read_next(), ParameterMode::new(val, 1)?),
-            )),
-            7 => Ok(LessThan(
-                Parameter(self.read_next(), ParameterMode::new(val, 0)?),
-                Parameter(self.read_next(), ParameterMode::new(val, 1)?),
-                Parameter(self.read_next(), ParameterMode::new(val, 2)?),
-            )),
-            8 => Ok(Equals(
-                Parameter(self.read_next(), ParameterMode::new(val, 0)?),
-                Parameter(self.read_next(), ParameterMode::new(val, 1)?),
-                Parameter(self.read_next(), ParameterMode::new(val, 2)?),
-            )),
-            9 => Ok(RelativeBase(Parameter(
-                self.read_next(),
-                ParameterMode::new(val, 0)?,
-            ))),
-            99 => Ok(Exit),
-            _ => Err(Error::OpcodeParseError(val)),
+    /// Decodes the instruction at `addr` without executing it.
+    pub fn inst_at(&self, addr: usize) -> Result<Instruction, Error> {
+        let mut pos = addr;
+        Instruction::parse_with(
+            || {
+                pos += 1;
+                self.peek(pos - 1)
+            },
+            self.opcodes(),
+        )
+    }
+
+    fn get_inst(&mut self) -> Result<Instruction, Error> {
+        let opcodes = self.opcodes.clone();
+        Instruction::parse_with(|| self.read_next(), opcodes.as_deref())
+    }
+
+    /// Consumes the next queued input on behalf of an instruction.
+    pub(crate) fn take_input(&mut self) -> Option<Int> {
+        let input = self.inputs.pop_front()?;
+        if let Some(trace) = &mut self.trace {
+            trace.record_input(input);
         }
+        if let Some(history) = &mut self.history {
+            history.record_input(input);
+        }
+        Some(input)
+    }
+
+    pub(crate) fn param_value(&mut self, param: &Parameter) -> Int {
+        self.get(param)
+    }
+
+    pub(crate) fn store_param(&mut self, param: &Parameter, val: Int) {
+        let addr = self.get_addr(param);
+        self.store(addr, val);
+    }
+
+    pub(crate) fn jump(&mut self, addr: usize) {
+        self.pos = addr;
     }
 
     fn get(&mut self, param: &Parameter) -> Int {
@@ -273,11 +899,32 @@ impl<'a> IntcodeComputer<'a> {
             Immediate => param.0,
             _ => {
                 let addr = self.get_addr(param);
+                if let Some(profile) = &mut self.profile {
+                    profile.record_read(addr);
+                }
                 self.read(addr)
             }
         }
     }
 
+    /// Writes on behalf of an instruction.
+    fn store(&mut self, addr: usize, val: Int) {
+        if let Some(profile) = &mut self.profile {
+            profile.record_write(addr);
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.record_write(addr, val);
+        }
+        if let Some((watched, written)) = &mut self.watch {
+            *written |= *watched == addr;
+        }
+        let old = self.peek(addr);
+        if let Some(history) = &mut self.history {
+            history.record_write(addr, old);
+        }
+        self.write(addr, val);
+    }
+
     fn get_addr(&mut self, param: &Parameter) -> usize {
         use ParameterMode::*;
 
@@ -288,10 +935,7 @@ impl<'a> IntcodeComputer<'a> {
     }
 
     pub fn read(&mut self, pos: usize) -> Int {
-        if pos >= self.mem.len() {
-            self.mem.resize((pos * 2).max(self.mem.len() * 2), 0);
-        }
-
+        self.grow(pos);
         self.mem[pos]
     }
 
@@ -301,21 +945,214 @@ impl<'a> IntcodeComputer<'a> {
         val
     }
 
-    pub fn write(&mut self, pos: usize, val: Int) -> () {
+    pub fn write(&mut self, pos: usize, val: Int) {
+        self.grow(pos);
+        self.mem[pos] = val;
+    }
+
+    /// Makes room for `pos`, doubling memory so repeated growth is cheap.
+    /// Strict machines never grow past their memory limit.
+    fn grow(&mut self, pos: usize) {
         if pos >= self.mem.len() {
-            self.mem.resize((pos * 2).max(self.mem.len() * 2), 0);
+            let mut len = (pos * 2).max(self.mem.len() * 2);
+            if let Some(strict) = self.strict {
+                len = len.min(strict.max_memory).max(pos + 1);
+            }
+            self.mem.resize(len, 0);
         }
-
-        self.mem[pos as usize] = val;
     }
 
-    pub fn inc(&mut self) -> () {
+    pub fn inc(&mut self) {
         self.pos += 1;
     }
 }
 
+impl<'a> IntcodeComputer<'a> {
+    pub fn new(input: &'a Receiver<Int>, output: &'a Sender<Int>) -> Self {
+        Self {
+            machine: Machine::new(),
+            input,
+            output,
+        }
+    }
+
+    pub fn create_io() -> (IOChannels, IOChannels) {
+        (bounded(BUFFER_SIZE), bounded(BUFFER_SIZE))
+    }
+
+    /// Attaches channels to an existing machine, such as one restored from
+    /// a `Snapshot`.
+    pub fn with_machine(
+        machine: Machine,
+        input: &'a Receiver<Int>,
+        output: &'a Sender<Int>,
+    ) -> Self {
+        Self {
+            machine,
+            input,
+            output,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn into_machine(self) -> Machine {
+        self.machine
+    }
+
+    /// Creates a copy of this computer's machine attached to different
+    /// channels, so it can carry on independently from the same point.
+    pub fn fork<'b>(
+        &self,
+        input: &'b Receiver<Int>,
+        output: &'b Sender<Int>,
+    ) -> IntcodeComputer<'b> {
+        IntcodeComputer::with_machine(self.machine.clone(), input, output)
+    }
+
+    /// Whether a value is waiting to be consumed by the next input instruction.
+    pub fn has_input(&self) -> bool {
+        self.machine.has_input() || !self.input.is_empty()
+    }
+
+    pub async fn repeat(&mut self) -> Result<i64, Error> {
+        let start_mem = self.mem.clone();
+
+        loop {
+            self.run().await?;
+            self.machine.state = OperationState::Ready;
+            self.machine.pos = 0;
+            self.machine.relative_base = 0;
+            self.machine.mem = start_mem.clone();
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<(), Error> {
+        loop {
+            self.step().await?;
+            if *self.state() == OperationState::Exited {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes the single instruction at `pos`, waiting on the input
+    /// channel if it needs a value.
+    pub async fn step(&mut self) -> Result<(), Error> {
+        step_with(&mut self.machine, &mut self.input, &mut self.output).await
+    }
+}
+
+/// Like `IntcodeComputer`, but owns its input and output so it can be
+/// moved into a task. By default these are channels, clones of which can be
+/// handed to any number of other processes to build chains, rings or
+/// fan-outs, but anything implementing `IntcodeInput` and `IntcodeOutput`
+/// will do.
 #[derive(Debug)]
-enum ParameterMode {
+pub struct IntcodeProcess<I = Receiver<Int>, O = Sender<Int>> {
+    machine: Machine,
+    input: I,
+    output: O,
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> IntcodeProcess<I, O> {
+    pub fn new(machine: Machine, input: I, output: O) -> Self {
+        Self {
+            machine,
+            input,
+            output,
+        }
+    }
+
+    /// Runs the program until it exits, then drops the input and output so
+    /// anyone waiting on this process's output channel sees it close.
+    pub async fn run(mut self) -> Result<Machine, Error> {
+        while self.machine.state != OperationState::Exited {
+            step_with(&mut self.machine, &mut self.input, &mut self.output).await?;
+        }
+
+        Ok(self.machine)
+    }
+}
+
+impl<I, O> IntcodeProcess<I, O>
+where
+    I: IntcodeInput + 'static,
+    O: IntcodeOutput + 'static,
+{
+    /// Runs the program on its own task, handing back the final machine
+    /// state once it exits.
+    pub fn spawn(self) -> JoinHandle<Result<Machine, Error>> {
+        task::spawn(self.run())
+    }
+}
+
+/// Executes a single instruction, reading from `input` or writing to
+/// `output` if it needs to.
+pub async fn step_with<I, O>(
+    machine: &mut Machine,
+    input: &mut I,
+    output: &mut O,
+) -> Result<(), Error>
+where
+    I: IntcodeInput + ?Sized,
+    O: IntcodeOutput + ?Sized,
+{
+    // A custom instruction can wait for input more than once, and still
+    // output once it has it
+    let mut status = machine.step()?;
+    while status == Status::AwaitingInput {
+        let val = recv_input(machine, input).await?;
+        machine.push_input(val);
+        status = machine.step()?;
+    }
+    if let Status::Output(val) = status {
+        output.send(val).await?;
+    }
+
+    Ok(())
+}
+
+/// Reads a value from `input`, giving up if the machine's timeout runs out
+/// first.
+async fn recv_input<I>(machine: &Machine, input: &mut I) -> Result<Int, Error>
+where
+    I: IntcodeInput + ?Sized,
+{
+    let timeout = machine.limits().and_then(|limits| limits.timeout);
+    match (timeout, machine.time_left()) {
+        (Some(timeout), Some(left)) => {
+            future::timeout(left, input.recv())
+                .await
+                .map_err(|_| Error::LimitExceeded {
+                    pos: machine.pos(),
+                    limit: Limit::Time(timeout),
+                })?
+        }
+        _ => input.recv().await,
+    }
+}
+
+impl Deref for IntcodeComputer<'_> {
+    type Target = Machine;
+
+    fn deref(&self) -> &Self::Target {
+        &self.machine
+    }
+}
+
+impl DerefMut for IntcodeComputer<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.machine
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterMode {
     Position,
     Immediate,
     Relative,
@@ -328,7 +1165,7 @@ impl ParameterMode {
         match val {
             2 => Ok(Relative),
             1 => Ok(Immediate),
-            0 | _ => Ok(Position),
+            _ => Ok(Position),
         }
     }
 
@@ -339,13 +1176,37 @@ impl ParameterMode {
     fn new(x: Int, pos: u32) -> Result<ParameterMode, Error> {
         Self::from_int(Self::parse_mode(x, pos))
     }
+
+    /// The digit this mode occupies in an instruction's opcode.
+    pub fn to_int(self) -> Int {
+        use ParameterMode::*;
+
+        match self {
+            Position => 0,
+            Immediate => 1,
+            Relative => 2,
+        }
+    }
 }
 
-#[derive(Debug)]
-struct Parameter(Int, ParameterMode);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parameter(pub Int, pub ParameterMode);
 
-#[derive(Debug)]
-enum Instruction {
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ParameterMode::*;
+
+        match self.1 {
+            Position => write!(f, "[{}]", self.0),
+            Immediate => write!(f, "#{}", self.0),
+            Relative if self.0 < 0 => write!(f, "rb{}", self.0),
+            Relative => write!(f, "rb+{}", self.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
     Add(Parameter, Parameter, Parameter),
     Multiply(Parameter, Parameter, Parameter),
     Input(Parameter),
@@ -356,6 +1217,139 @@ enum Instruction {
     Equals(Parameter, Parameter, Parameter),
     RelativeBase(Parameter),
     Exit,
+    /// An opcode registered with `Opcodes`.
+    Custom {
+        opcode: Int,
+        mnemonic: &'static str,
+        params: Vec<Parameter>,
+    },
+}
+
+impl Instruction {
+    /// Decodes a single instruction, pulling its opcode and then each of
+    /// its parameters from `next`.
+    pub fn parse<F>(next: F) -> Result<Instruction, Error>
+    where
+        F: FnMut() -> Int,
+    {
+        Self::parse_with(next, None)
+    }
+
+    /// Like `parse`, but also decodes the custom opcodes in `opcodes`.
+    pub fn parse_with<F>(mut next: F, opcodes: Option<&Opcodes>) -> Result<Instruction, Error>
+    where
+        F: FnMut() -> Int,
+    {
+        use Instruction::*;
+
+        let val = next();
+        let opcode = val % 100;
+
+        let mut param = |pos| -> Result<Parameter, Error> {
+            let mode = ParameterMode::new(val, pos)?;
+            Ok(Parameter(next(), mode))
+        };
+
+        match opcode {
+            1 => Ok(Add(param(0)?, param(1)?, param(2)?)),
+            2 => Ok(Multiply(param(0)?, param(1)?, param(2)?)),
+            3 => Ok(Input(param(0)?)),
+            4 => Ok(Output(param(0)?)),
+            5 => Ok(JumpIfTrue(param(0)?, param(1)?)),
+            6 => Ok(JumpIfFalse(param(0)?, param(1)?)),
+            7 => Ok(LessThan(param(0)?, param(1)?, param(2)?)),
+            8 => Ok(Equals(param(0)?, param(1)?, param(2)?)),
+            9 => Ok(RelativeBase(param(0)?)),
+            99 => Ok(Exit),
+            _ => match opcodes.and_then(|ops| ops.get(opcode)) {
+                Some(op) => Ok(Custom {
+                    opcode,
+                    mnemonic: op.mnemonic,
+                    params: (0..op.arity as u32)
+                        .map(param)
+                        .collect::<Result<Vec<Parameter>, Error>>()?,
+                }),
+                None => Err(Error::OpcodeParseError(val)),
+            },
+        }
+    }
+
+    pub fn opcode(&self) -> Int {
+        use Instruction::*;
+
+        match self {
+            Add(..) => 1,
+            Multiply(..) => 2,
+            Input(..) => 3,
+            Output(..) => 4,
+            JumpIfTrue(..) => 5,
+            JumpIfFalse(..) => 6,
+            LessThan(..) => 7,
+            Equals(..) => 8,
+            RelativeBase(..) => 9,
+            Exit => 99,
+            Custom { opcode, .. } => *opcode,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        use Instruction::*;
+
+        match self {
+            Add(..) => "add",
+            Multiply(..) => "mul",
+            Input(..) => "in",
+            Output(..) => "out",
+            JumpIfTrue(..) => "jt",
+            JumpIfFalse(..) => "jf",
+            LessThan(..) => "lt",
+            Equals(..) => "eq",
+            RelativeBase(..) => "rbo",
+            Exit => "hlt",
+            Custom { mnemonic, .. } => mnemonic,
+        }
+    }
+
+    pub fn params(&self) -> Vec<&Parameter> {
+        use Instruction::*;
+
+        match self {
+            Add(a, b, c) | Multiply(a, b, c) | LessThan(a, b, c) | Equals(a, b, c) => {
+                vec![a, b, c]
+            }
+            JumpIfTrue(a, b) | JumpIfFalse(a, b) => vec![a, b],
+            Input(a) | Output(a) | RelativeBase(a) => vec![a],
+            Exit => vec![],
+            Custom { params, .. } => params.iter().collect(),
+        }
+    }
+
+    /// Number of memory cells the instruction occupies, including its opcode.
+    pub fn size(&self) -> usize {
+        self.params().len() + 1
+    }
+
+    /// Encodes the instruction back into its opcode and parameters.
+    pub fn encode(&self) -> Vec<Int> {
+        let params = self.params();
+        let modes = params.iter().enumerate().fold(0, |acc, (i, p)| {
+            acc + p.1.to_int() * (10 as Int).pow(i as u32)
+        });
+
+        let mut out = vec![modes * 100 + self.opcode()];
+        out.extend(params.iter().map(|p| p.0));
+        out
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        for (i, param) in self.params().iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, param)?;
+        }
+        Ok(())
+    }
 }
 
 #[test]
@@ -422,6 +1416,139 @@ fn day_5_examples_work() {
     // TODO: Add rest of day 5 tests
 }
 
+#[test]
+fn day_5_comparisons_work() {
+    for (program, input, expected) in [
+        ("3,9,8,9,10,9,4,9,99,-1,8", 8, 1),
+        ("3,9,8,9,10,9,4,9,99,-1,8", 7, 0),
+        ("3,3,1107,-1,8,3,4,3,99", 5, 1),
+        ("3,3,1107,-1,8,3,4,3,99", 9, 0),
+        ("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", 0, 0),
+        ("3,3,1105,-1,9,1101,0,0,12,4,12,99,1", 3, 1),
+    ] {
+        let mut machine = Machine::new();
+        machine.init(program).unwrap();
+        assert_eq!(machine.run_with_input([input]).unwrap(), vec![expected]);
+    }
+}
+
+#[test]
+fn machines_run_without_an_executor() {
+    use rayon::prelude::*;
+
+    let mut machine = Machine::new();
+    machine
+        .init("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99")
+        .unwrap();
+
+    let outputs: Vec<Int> = (7..=9)
+        .into_par_iter()
+        .map(|input| {
+            let mut machine = machine.clone();
+            machine.run_with_input([input]).unwrap()[0]
+        })
+        .collect();
+    assert_eq!(outputs, vec![999, 1000, 1001]);
+}
+
+#[test]
+fn resume_stops_for_io() {
+    let mut machine = Machine::new();
+    machine.init("3,0,4,0,99").unwrap();
+
+    assert_eq!(machine.resume().unwrap(), Status::AwaitingInput);
+    assert_eq!(machine.pos(), 0);
+
+    machine.push_input(7);
+    assert_eq!(machine.resume().unwrap(), Status::Output(7));
+    assert_eq!(machine.resume().unwrap(), Status::Halted);
+    assert_eq!(machine.step().unwrap(), Status::Halted);
+}
+
+#[test]
+fn snapshots_restore_and_fork() {
+    use async_std::task;
+
+    // Outputs its input doubled, forever
+    let mut machine = Machine::new();
+    machine.init("3,100,1002,100,2,100,4,100,1105,1,0").unwrap();
+    machine.push_input(1);
+    assert_eq!(machine.resume().unwrap(), Status::Output(2));
+    machine.push_input(5);
+
+    let snapshot = Snapshot::from_str(&machine.snapshot().to_string()).unwrap();
+    assert_eq!(snapshot, machine.snapshot());
+    assert_eq!(snapshot.inputs, vec![5]);
+
+    assert_eq!(machine.resume().unwrap(), Status::Output(10));
+
+    let mut restored = Machine::from(snapshot.clone());
+    assert_eq!(restored.resume().unwrap(), Status::Output(10));
+
+    machine.restore(&snapshot);
+    let (inputs, outputs) = IntcodeComputer::create_io();
+    let computer = IntcodeComputer::with_machine(machine, &inputs.1, &outputs.0);
+    let (fork_inputs, fork_outputs) = IntcodeComputer::create_io();
+    let mut fork = computer.fork(&fork_inputs.1, &fork_outputs.0);
+
+    task::block_on(async {
+        fork.step().await.unwrap();
+        fork_inputs.0.send(7).await.unwrap();
+        while fork_outputs.1.len() < 2 {
+            fork.step().await.unwrap();
+        }
+    });
+    assert_eq!(fork_outputs.1.try_recv(), Ok(10));
+    assert_eq!(fork_outputs.1.try_recv(), Ok(14));
+    assert_eq!(computer.snapshot(), snapshot);
+}
+
+#[test]
+fn processes_can_be_spawned_in_a_ring() {
+    use futures::future::join_all;
+
+    // Day 7's feedback loop example
+    let mut amp = Machine::new();
+    amp.init(
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+    )
+    .unwrap();
+    let phases = [9, 8, 7, 6, 5];
+
+    let channels: Vec<IOChannels> = phases.iter().map(|_| bounded(BUFFER_SIZE)).collect();
+    let handles: Vec<JoinHandle<Result<Machine, Error>>> = (0..phases.len())
+        .map(|i| {
+            let input = channels[i].1.clone();
+            let output = channels[(i + 1) % phases.len()].0.clone();
+            IntcodeProcess::new(amp.clone(), input, output).spawn()
+        })
+        .collect();
+
+    let output = task::block_on(async {
+        for (channel, phase) in channels.iter().zip(phases) {
+            channel.0.send(phase).await.unwrap();
+        }
+        channels[0].0.send(0).await.unwrap();
+
+        for machine in join_all(handles).await {
+            assert_eq!(*machine.unwrap().state(), OperationState::Exited);
+        }
+        channels[0].1.recv().await.unwrap()
+    });
+    assert_eq!(output, 139629729);
+}
+
+#[test]
+fn process_output_closes_when_it_exits() {
+    let mut machine = Machine::new();
+    machine.init("104,1,104,2,99").unwrap();
+    let ((_in_sender, in_receiver), (out_sender, out_receiver)) = IntcodeComputer::create_io();
+    let handle = IntcodeProcess::new(machine, in_receiver, out_sender).spawn();
+
+    assert_eq!(task::block_on(get_all_outputs(&out_receiver)), vec![1, 2]);
+    assert!(task::block_on(handle).is_ok());
+}
+
 #[test]
 fn relative_base_works() {
     use async_std::task;
@@ -447,10 +1574,158 @@ fn relative_base_works() {
     );
 }
 
+#[test]
+fn strict_machines_fault() {
+    let run = |program: &str, input: Vec<Int>| {
+        let mut machine = Machine::new();
+        machine.init(program).unwrap();
+        machine.enable_strict(StrictMode { max_memory: 64 });
+        machine.run_with_input(input)
+    };
+    let fault = |program: &str, input: Vec<Int>| match run(program, input) {
+        Err(Error::Fault { pos, fault, .. }) => (pos, fault),
+        other => panic!("expected a fault, got {:?}", other),
+    };
+
+    assert_eq!(run("1,0,0,0,99", vec![]).unwrap(), vec![]);
+    assert_eq!(
+        fault("109,-5,204,2,99", vec![]),
+        (2, Fault::NegativeAddress(-3))
+    );
+    assert_eq!(fault("4,64,99", vec![]), (0, Fault::OutOfMemory(64)));
+    assert_eq!(
+        fault("104,1,304,0,99", vec![]),
+        (2, Fault::InvalidParameterMode(3))
+    );
+    assert_eq!(fault("10099", vec![]), (0, Fault::InvalidParameterMode(1)));
+    assert_eq!(fault("11101,1,1,3,99", vec![]), (0, Fault::ImmediateWrite));
+    assert_eq!(
+        fault("1105,1,-1,99", vec![]),
+        (0, Fault::JumpOutOfMemory(-1))
+    );
+    assert_eq!(fault("1105,1,4,99", vec![]), (0, Fault::JumpOutOfMemory(4)));
+    // Not taken, so it doesn't matter where it would have gone
+    assert_eq!(run("1105,0,-1,99", vec![]).unwrap(), vec![]);
+
+    match run("1,0,0,3,11101,1,1,3,99", vec![]) {
+        Err(Error::Fault { pos, inst, fault }) => {
+            assert_eq!(pos, 4);
+            assert_eq!(inst.to_string(), "add #1, #1, #3");
+            assert_eq!(fault, Fault::ImmediateWrite);
+        }
+        other => panic!("expected a fault, got {:?}", other),
+    }
+
+    // Memory grows up to the limit, but not past it
+    let mut machine = Machine::new();
+    machine.init("1101,1,1,40,99").unwrap();
+    machine.enable_strict(StrictMode { max_memory: 50 });
+    machine.run_with_input(vec![]).unwrap();
+    assert_eq!(machine.mem().len(), 50);
+
+    // A lenient machine keeps the old behaviour
+    let mut machine = Machine::new();
+    machine.init("11101,1,1,3,99").unwrap();
+    assert_eq!(machine.run_with_input(vec![]).unwrap(), vec![]);
+    assert_eq!(machine.peek(3), 2);
+}
+
+#[test]
+fn limits_stop_runaway_programs() {
+    // Adds 1 to [7] forever
+    let mut machine = Machine::new();
+    machine.init("1001,7,1,7,1105,1,0,0").unwrap();
+    machine.set_limits(Limits {
+        max_steps: Some(10),
+        timeout: None,
+    });
+
+    match machine.resume() {
+        Err(Error::LimitExceeded { pos, limit }) => {
+            assert_eq!(pos, 0);
+            assert_eq!(limit, Limit::Steps(10));
+        }
+        other => panic!("expected to hit the limit, got {:?}", other),
+    }
+    assert_eq!(machine.steps_used(), Some(10));
+    assert_eq!(machine.peek(7), 5);
+
+    machine.set_limits(Limits {
+        max_steps: Some(4),
+        timeout: None,
+    });
+    assert!(machine.resume().is_err());
+    assert_eq!(machine.peek(7), 7);
+
+    machine.set_limits(Limits {
+        max_steps: None,
+        timeout: Some(Duration::from_millis(10)),
+    });
+    assert!(matches!(
+        machine.resume(),
+        Err(Error::LimitExceeded {
+            limit: Limit::Time(_),
+            ..
+        })
+    ));
+
+    // Repeating a program that exits still stops
+    let (inputs, outputs) = IntcodeComputer::create_io();
+    let mut computer = IntcodeComputer::new(&inputs.1, &outputs.0);
+    computer.init("1101,1,1,5,99,0").unwrap();
+    computer.set_limits(Limits {
+        max_steps: Some(6),
+        timeout: None,
+    });
+    assert!(matches!(
+        task::block_on(computer.repeat()),
+        Err(Error::LimitExceeded { pos: 0, .. })
+    ));
+
+    // So does waiting on input that never comes
+    let (_in_sender, in_receiver) = bounded(1);
+    let (out_sender, _out_receiver) = bounded(1);
+    let mut machine = Machine::new();
+    machine.init("3,0,99").unwrap();
+    machine.set_limits(Limits {
+        max_steps: None,
+        timeout: Some(Duration::from_millis(10)),
+    });
+    let process = IntcodeProcess::new(machine, in_receiver, out_sender);
+    assert!(matches!(
+        task::block_on(process.run()),
+        Err(Error::LimitExceeded {
+            pos: 0,
+            limit: Limit::Time(_),
+        })
+    ));
+}
+
 #[derive(Debug)]
 pub enum Error {
     ProgramParseError(String),
     OpcodeParseError(Int),
     InvalidInputError(String),
     InvalidOutputError(String),
+    SnapshotParseError(String),
+    TraceParseError(String),
+    ReplayError(String),
+    HistoryError(String),
+    /// The machine ran into `limit` before executing the instruction at
+    /// `pos`.
+    LimitExceeded {
+        pos: usize,
+        limit: Limit,
+    },
+    /// A strict machine refused to execute `inst` at `pos`.
+    Fault {
+        pos: usize,
+        inst: Instruction,
+        fault: Fault,
+    },
+    AssemblyError {
+        line: usize,
+        column: usize,
+        message: String,
+    },
 }
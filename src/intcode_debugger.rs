@@ -0,0 +1,254 @@
+use crate::intcode_computer::{Error, Instruction, Int, IntcodeComputer, OperationState};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Why the debugger handed control back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    Step,
+    AwaitingInput,
+    Breakpoint(usize),
+    Watchpoint { addr: usize, old: Int, new: Int },
+    Exited,
+}
+
+/// Drives an `IntcodeComputer` one instruction at a time, stopping at
+/// breakpoints and whenever a watched memory cell changes value.
+#[derive(Debug)]
+pub struct Debugger<'a> {
+    computer: IntcodeComputer<'a>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeMap<usize, Int>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(computer: IntcodeComputer<'a>) -> Self {
+        Self {
+            computer,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+        }
+    }
+
+    pub fn computer(&self) -> &IntcodeComputer<'a> {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut IntcodeComputer<'a> {
+        &mut self.computer
+    }
+
+    pub fn into_inner(self) -> IntcodeComputer<'a> {
+        self.computer
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &usize> {
+        self.breakpoints.iter()
+    }
+
+    /// Returns false if there was already a breakpoint at `addr`.
+    pub fn add_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.insert(addr)
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &usize> {
+        self.watchpoints.keys()
+    }
+
+    /// Returns false if `addr` was already being watched.
+    pub fn add_watchpoint(&mut self, addr: usize) -> bool {
+        let val = self.computer.peek(addr);
+        self.watchpoints.insert(addr, val).is_none()
+    }
+
+    pub fn remove_watchpoint(&mut self, addr: usize) -> bool {
+        self.watchpoints.remove(&addr).is_some()
+    }
+
+    /// Executes a single instruction. Input instructions are only executed
+    /// once a value is waiting for them, so the caller gets a chance to
+    /// provide one instead of blocking forever.
+    pub async fn step(&mut self) -> Result<StopReason, Error> {
+        if *self.computer.state() == OperationState::Exited {
+            return Ok(StopReason::Exited);
+        }
+        if let Instruction::Input(_) = self.computer.next_inst()? {
+            if !self.computer.has_input() {
+                return Ok(StopReason::AwaitingInput);
+            }
+        }
+
+        self.computer.step().await?;
+
+        if *self.computer.state() == OperationState::Exited {
+            return Ok(StopReason::Exited);
+        }
+
+        for (addr, old) in self.watchpoints.iter_mut() {
+            let new = self.computer.peek(*addr);
+            if new != *old {
+                let old = std::mem::replace(old, new);
+                return Ok(StopReason::Watchpoint {
+                    addr: *addr,
+                    old,
+                    new,
+                });
+            }
+        }
+
+        Ok(StopReason::Step)
+    }
+
+    /// Undoes the last instruction, returning false if there's nothing left
+    /// to undo. Needs the machine's history to be enabled.
+    pub fn step_back(&mut self) -> Result<bool, Error> {
+        let stepped = self.computer.step_back()?;
+        self.refresh_watchpoints();
+        Ok(stepped)
+    }
+
+    /// Rewinds to just before the last instruction that wrote to `addr`,
+    /// returning where that instruction is, or `None` if nothing has.
+    pub fn rewind_to_write(&mut self, addr: usize) -> Result<Option<usize>, Error> {
+        let found = self.computer.last_write(addr)?;
+        if let Some((step, _)) = found {
+            self.computer.rewind_to(step)?;
+            self.refresh_watchpoints();
+        }
+        Ok(found.map(|(_, pos)| pos))
+    }
+
+    fn refresh_watchpoints(&mut self) {
+        for (addr, val) in self.watchpoints.iter_mut() {
+            *val = self.computer.peek(*addr);
+        }
+    }
+
+    /// Runs until a breakpoint or watchpoint is hit, the program needs input
+    /// or it exits.
+    /// Always executes at least one instruction, so continuing from a
+    /// breakpoint doesn't immediately stop at it again.
+    pub async fn cont(&mut self) -> Result<StopReason, Error> {
+        loop {
+            match self.step().await? {
+                StopReason::Step => {
+                    let pos = self.computer.pos();
+                    if self.breakpoints.contains(&pos) {
+                        return Ok(StopReason::Breakpoint(pos));
+                    }
+                }
+                reason => return Ok(reason),
+            }
+        }
+    }
+}
+
+#[test]
+fn stops_at_breakpoints() {
+    use async_std::task;
+
+    let (inputs, outputs) = IntcodeComputer::create_io();
+    let mut computer = IntcodeComputer::new(&inputs.1, &outputs.0);
+    // Counts [9] down from 3 to 0
+    computer.init("1001,9,-1,9,1005,9,0,99,0,3").unwrap();
+    let mut debugger = Debugger::new(computer);
+    debugger.add_breakpoint(4);
+
+    for expected in (0..3).rev() {
+        assert_eq!(
+            task::block_on(debugger.cont()).unwrap(),
+            StopReason::Breakpoint(4)
+        );
+        assert_eq!(debugger.computer().peek(9), expected);
+    }
+
+    assert_eq!(task::block_on(debugger.cont()).unwrap(), StopReason::Exited);
+}
+
+#[test]
+fn waits_for_input() {
+    use async_std::task;
+
+    let (inputs, outputs) = IntcodeComputer::create_io();
+    let mut computer = IntcodeComputer::new(&inputs.1, &outputs.0);
+    computer.init("3,5,4,5,99").unwrap();
+    let mut debugger = Debugger::new(computer);
+
+    assert_eq!(
+        task::block_on(debugger.cont()).unwrap(),
+        StopReason::AwaitingInput
+    );
+    assert_eq!(debugger.computer().pos(), 0);
+
+    task::block_on(inputs.0.send(42)).unwrap();
+    assert_eq!(task::block_on(debugger.cont()).unwrap(), StopReason::Exited);
+    assert_eq!(outputs.1.try_recv(), Ok(42));
+}
+
+#[test]
+fn stops_when_watched_memory_changes() {
+    use async_std::task;
+
+    let (inputs, outputs) = IntcodeComputer::create_io();
+    let mut computer = IntcodeComputer::new(&inputs.1, &outputs.0);
+    computer
+        .init("1101,0,7,13,1101,2,3,13,1101,2,3,13,99")
+        .unwrap();
+    let mut debugger = Debugger::new(computer);
+    debugger.add_watchpoint(13);
+
+    assert_eq!(
+        task::block_on(debugger.cont()).unwrap(),
+        StopReason::Watchpoint {
+            addr: 13,
+            old: 0,
+            new: 7
+        }
+    );
+    assert_eq!(
+        task::block_on(debugger.cont()).unwrap(),
+        StopReason::Watchpoint {
+            addr: 13,
+            old: 7,
+            new: 5
+        }
+    );
+    assert_eq!(task::block_on(debugger.step()).unwrap(), StopReason::Step);
+    assert_eq!(debugger.computer().pos(), 12);
+    assert_eq!(task::block_on(debugger.cont()).unwrap(), StopReason::Exited);
+}
+
+#[test]
+fn steps_backwards() {
+    use async_std::task;
+
+    let (inputs, outputs) = IntcodeComputer::create_io();
+    let mut computer = IntcodeComputer::new(&inputs.1, &outputs.0);
+    computer
+        .init("1101,0,7,13,1101,2,3,13,1101,2,3,13,99")
+        .unwrap();
+    computer.enable_history(100, 100);
+    let mut debugger = Debugger::new(computer);
+    debugger.add_watchpoint(13);
+
+    while task::block_on(debugger.cont()).unwrap() != StopReason::Exited {}
+    assert_eq!(debugger.rewind_to_write(13).unwrap(), Some(8));
+    assert_eq!(debugger.computer().pos(), 8);
+    assert_eq!(debugger.computer().peek(13), 5);
+
+    assert!(debugger.step_back().unwrap());
+    assert_eq!(debugger.computer().pos(), 4);
+    assert_eq!(debugger.computer().peek(13), 7);
+    assert_eq!(
+        task::block_on(debugger.step()).unwrap(),
+        StopReason::Watchpoint {
+            addr: 13,
+            old: 7,
+            new: 5
+        }
+    );
+}
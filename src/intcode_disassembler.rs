@@ -0,0 +1,389 @@
+use crate::intcode_computer::{Instruction, Int, Parameter, ParameterMode};
+use crate::intcode_opcodes::Opcodes;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Maximum number of values printed on a single `data` line.
+const DATA_PER_LINE: usize = 8;
+
+/// Column the raw cells comment starts at in a listing.
+const COMMENT_COLUMN: usize = 40;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    Code { addr: usize, inst: Instruction },
+    Data { addr: usize, values: Vec<Int> },
+}
+
+impl Entry {
+    pub fn addr(&self) -> usize {
+        match self {
+            Entry::Code { addr, .. } | Entry::Data { addr, .. } => *addr,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Entry::Code { inst, .. } => inst.size(),
+            Entry::Data { values, .. } => values.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Disassembly {
+    entries: Vec<Entry>,
+    labels: BTreeMap<usize, String>,
+}
+
+impl Disassembly {
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn label(&self, addr: usize) -> Option<&str> {
+        self.labels.get(&addr).map(|s| s.as_str())
+    }
+
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.entries.iter().any(|entry| match entry {
+            Entry::Code { addr: start, inst } => (*start..start + inst.size()).contains(&addr),
+            _ => false,
+        })
+    }
+
+    /// Renders a single entry as a listing line, without its label.
+    pub fn format_entry(&self, entry: &Entry) -> String {
+        match entry {
+            Entry::Code { addr, inst } => {
+                let operands: Vec<String> = inst
+                    .params()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, param)| self.format_param(inst, i, param))
+                    .collect();
+
+                let text = if operands.is_empty() {
+                    format!("{:04}: {}", addr, inst.mnemonic())
+                } else {
+                    format!("{:04}: {} {}", addr, inst.mnemonic(), operands.join(", "))
+                };
+
+                let raw: Vec<String> = inst.encode().iter().map(|i| i.to_string()).collect();
+                format!(
+                    "{:width$} ; {}",
+                    text,
+                    raw.join(","),
+                    width = COMMENT_COLUMN
+                )
+            }
+            Entry::Data { addr, values } => {
+                let values: Vec<String> = values.iter().map(|i| i.to_string()).collect();
+                format!("{:04}: data {}", addr, values.join(", "))
+            }
+        }
+    }
+
+    fn format_param(&self, inst: &Instruction, index: usize, param: &Parameter) -> String {
+        let is_jump_target = matches!(
+            inst,
+            Instruction::JumpIfTrue(..) | Instruction::JumpIfFalse(..)
+        ) && index == 1;
+
+        match (param.1, usize::try_from(param.0).ok()) {
+            (ParameterMode::Immediate, Some(addr)) if is_jump_target => match self.label(addr) {
+                Some(label) => format!("#{}", label),
+                None => param.to_string(),
+            },
+            (ParameterMode::Position, Some(addr)) => match self.label(addr) {
+                Some(label) => format!("[{}]", label),
+                None => param.to_string(),
+            },
+            _ => param.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in self.entries.iter() {
+            if let Some(label) = self.label(entry.addr()) {
+                writeln!(f, "{}:", label)?;
+            }
+            writeln!(f, "      {}", self.format_entry(entry))?;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes the instruction at `addr`, or `None` if it is invalid or runs
+/// past the end of the program.
+fn decode_at(program: &[Int], addr: usize, opcodes: Option<&Opcodes>) -> Option<Instruction> {
+    let mut i = addr;
+    let mut truncated = false;
+    let inst = Instruction::parse_with(
+        || {
+            let val = program.get(i).copied().unwrap_or_else(|| {
+                truncated = true;
+                0
+            });
+            i += 1;
+            val
+        },
+        opcodes,
+    )
+    .ok()?;
+
+    if truncated {
+        None
+    } else {
+        Some(inst)
+    }
+}
+
+fn immediate_addr(param: &Parameter) -> Option<usize> {
+    match param.1 {
+        ParameterMode::Immediate => usize::try_from(param.0).ok(),
+        _ => None,
+    }
+}
+
+/// Walks every path reachable from the entry point, following jumps whose
+/// targets are known statically. Immediate values pushed onto the stack
+/// (`add #x, #0, rb+n` and friends) are treated as return addresses.
+///
+/// Returns the starting address of every instruction found, and the
+/// addresses that are jumped to.
+fn trace_code(
+    program: &[Int],
+    opcodes: Option<&Opcodes>,
+) -> (BTreeMap<usize, Instruction>, BTreeSet<usize>) {
+    use Instruction::*;
+
+    let mut code: BTreeMap<usize, Instruction> = BTreeMap::new();
+    let mut covered = vec![false; program.len()];
+    let mut targets = BTreeSet::new();
+    let mut pending = vec![0];
+
+    while let Some(mut addr) = pending.pop() {
+        while addr < program.len() && !code.contains_key(&addr) {
+            let inst = match decode_at(program, addr, opcodes) {
+                Some(inst) => inst,
+                None => break,
+            };
+            if covered[addr..addr + inst.size()].iter().any(|c| *c) {
+                break;
+            }
+
+            for c in covered[addr..addr + inst.size()].iter_mut() {
+                *c = true;
+            }
+
+            let next = addr + inst.size();
+            let falls_through = match &inst {
+                JumpIfTrue(cond, dest) | JumpIfFalse(cond, dest) => {
+                    let always = match (&inst, cond.1) {
+                        (JumpIfTrue(..), ParameterMode::Immediate) => Some(cond.0 != 0),
+                        (JumpIfFalse(..), ParameterMode::Immediate) => Some(cond.0 == 0),
+                        _ => None,
+                    };
+                    if always != Some(false) {
+                        if let Some(target) = immediate_addr(dest) {
+                            targets.insert(target);
+                            pending.push(target);
+                        }
+                    }
+                    always != Some(true)
+                }
+                Add(lhs, rhs, Parameter(_, ParameterMode::Relative))
+                | Multiply(lhs, rhs, Parameter(_, ParameterMode::Relative)) => {
+                    if let (Some(lhs), Some(rhs)) = (immediate_addr(lhs), immediate_addr(rhs)) {
+                        let ret = if let Add(..) = inst {
+                            lhs.checked_add(rhs)
+                        } else {
+                            lhs.checked_mul(rhs)
+                        };
+                        let ret = ret.filter(|ret| {
+                            *ret < program.len() && decode_at(program, *ret, opcodes).is_some()
+                        });
+                        if let Some(ret) = ret {
+                            targets.insert(ret);
+                            pending.push(ret);
+                        }
+                    }
+                    true
+                }
+                Exit => false,
+                _ => true,
+            };
+
+            code.insert(addr, inst);
+            if !falls_through {
+                break;
+            }
+            addr = next;
+        }
+    }
+
+    targets.retain(|addr| code.contains_key(addr));
+    (code, targets)
+}
+
+/// Splits a program into code and data regions and decodes its instructions.
+pub fn disassemble(program: &[Int]) -> Disassembly {
+    disassemble_impl(program, None)
+}
+
+/// Like `disassemble`, but also decodes the custom opcodes in `opcodes`.
+pub fn disassemble_with(program: &[Int], opcodes: &Opcodes) -> Disassembly {
+    disassemble_impl(program, Some(opcodes))
+}
+
+fn disassemble_impl(program: &[Int], opcodes: Option<&Opcodes>) -> Disassembly {
+    let (mut code, targets) = trace_code(program, opcodes);
+
+    let mut entries = Vec::new();
+    let mut labels = BTreeMap::new();
+    for addr in targets {
+        labels.insert(addr, format!("loc_{}", addr));
+    }
+
+    let mut data_refs = BTreeSet::new();
+    for inst in code.values() {
+        for param in inst.params() {
+            if param.1 == ParameterMode::Position {
+                if let Ok(addr) = usize::try_from(param.0) {
+                    data_refs.insert(addr);
+                }
+            }
+        }
+    }
+
+    let mut addr = 0;
+    let mut data: Option<(usize, Vec<Int>)> = None;
+    while addr < program.len() {
+        if let Some(inst) = code.remove(&addr) {
+            if let Some((start, values)) = data.take() {
+                entries.push(Entry::Data {
+                    addr: start,
+                    values,
+                });
+            }
+            addr += inst.size();
+            entries.push(Entry::Code {
+                addr: addr - inst.size(),
+                inst,
+            });
+            continue;
+        }
+
+        if data_refs.contains(&addr) {
+            labels.insert(addr, format!("var_{}", addr));
+        }
+
+        let split = match &data {
+            Some((_, values)) => values.len() >= DATA_PER_LINE || labels.contains_key(&addr),
+            None => false,
+        };
+        if split {
+            let (start, values) = data.take().unwrap();
+            entries.push(Entry::Data {
+                addr: start,
+                values,
+            });
+        }
+
+        data.get_or_insert_with(|| (addr, Vec::new()))
+            .1
+            .push(program[addr]);
+        addr += 1;
+    }
+
+    if let Some((start, values)) = data {
+        entries.push(Entry::Data {
+            addr: start,
+            values,
+        });
+    }
+
+    Disassembly { entries, labels }
+}
+
+#[test]
+fn separates_code_from_data() {
+    let disassembly = disassemble(&[1001, 7, 3, 7, 4, 7, 99, 33]);
+
+    assert_eq!(
+        disassembly.entries(),
+        &[
+            Entry::Code {
+                addr: 0,
+                inst: Instruction::Add(
+                    Parameter(7, ParameterMode::Position),
+                    Parameter(3, ParameterMode::Immediate),
+                    Parameter(7, ParameterMode::Position),
+                ),
+            },
+            Entry::Code {
+                addr: 4,
+                inst: Instruction::Output(Parameter(7, ParameterMode::Position)),
+            },
+            Entry::Code {
+                addr: 6,
+                inst: Instruction::Exit,
+            },
+            Entry::Data {
+                addr: 7,
+                values: vec![33],
+            },
+        ]
+    );
+    assert!(disassembly.is_code(5));
+    assert!(!disassembly.is_code(7));
+}
+
+#[test]
+fn follows_jumps_and_labels_targets() {
+    // Jumps over a block of data to the halt instruction.
+    let disassembly = disassemble(&[1105, 1, 5, 12, 34, 204, -1, 99]);
+
+    assert_eq!(disassembly.label(5), Some("loc_5"));
+    assert!(!disassembly.is_code(3));
+    assert!(disassembly.is_code(5));
+    assert_eq!(
+        disassembly.to_string(),
+        "      0000: jt #1, #loc_5                      ; 1105,1,5
+      0003: data 12, 34
+loc_5:
+      0005: out rb-1                           ; 204,-1
+      0007: hlt                                ; 99
+"
+    );
+}
+
+#[test]
+fn names_referenced_data() {
+    let disassembly = disassemble(&[4, 3, 99, 42]);
+
+    assert_eq!(
+        disassembly.to_string(),
+        "      0000: out [var_3]                        ; 4,3
+      0002: hlt                                ; 99
+var_3:
+      0003: data 42
+"
+    );
+}
+
+#[test]
+fn ignores_return_addresses_that_overflow() {
+    for program in [
+        "21101,9223372036854775807,9223372036854775807,0,99",
+        "21102,9223372036854775807,3,0,99",
+    ] {
+        let program: Vec<Int> = program.split(',').map(|x| x.parse().unwrap()).collect();
+        let disassembly = disassemble(&program);
+        assert!(disassembly.to_string().contains("hlt"));
+    }
+}
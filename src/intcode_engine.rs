@@ -0,0 +1,355 @@
+use crate::intcode_computer::{
+    Error, Instruction, Int, IntVec, Machine, OperationState, ParameterMode, Snapshot, Status,
+};
+use crate::intcode_disassembler::{disassemble, Entry};
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+/// An instruction decoded into a flat form that's cheap to copy.
+#[derive(Debug, Clone, Copy)]
+struct Op {
+    opcode: Int,
+    args: [Int; 3],
+    modes: [ParameterMode; 3],
+    size: usize,
+}
+
+impl From<&Instruction> for Op {
+    fn from(inst: &Instruction) -> Self {
+        let mut args = [0; 3];
+        let mut modes = [ParameterMode::Position; 3];
+        for (i, param) in inst.params().into_iter().enumerate() {
+            args[i] = param.0;
+            modes[i] = param.1;
+        }
+
+        Op {
+            opcode: inst.opcode(),
+            args,
+            modes,
+            size: inst.size(),
+        }
+    }
+}
+
+/// Runs Intcode programs like `Machine`, but decodes each instruction only
+/// once, keeping the decoded form in a cache alongside memory. Writes into
+/// memory covered by a cached instruction evict it, so self-modifying code
+/// is decoded again from memory the next time it runs.
+///
+/// Cloning an engine keeps its cache, so running one program many times
+/// with different inputs only pays for decoding once.
+#[derive(Debug, Clone, Default)]
+pub struct Engine {
+    state: OperationState,
+    mem: Vec<Int>,
+    pos: usize,
+    relative_base: isize,
+    inputs: VecDeque<Int>,
+    ops: Vec<Option<Op>>,
+    invalidations: u64,
+}
+
+impl Engine {
+    pub fn new(program: &str) -> Result<Self, Error> {
+        let mut engine = Self::from(Snapshot {
+            state: OperationState::Ready,
+            mem: IntVec::from_str(program)?.into_inner(),
+            pos: 0,
+            relative_base: 0,
+            inputs: Vec::new(),
+        });
+        engine.compile();
+        Ok(engine)
+    }
+
+    pub fn state(&self) -> &OperationState {
+        &self.state
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn mem(&self) -> &[Int] {
+        &self.mem
+    }
+
+    /// How many cached instructions have been evicted by writes.
+    pub fn invalidations(&self) -> u64 {
+        self.invalidations
+    }
+
+    /// Decodes every instruction the disassembler can find ahead of time.
+    /// Anything it misses is decoded when it first runs.
+    pub fn compile(&mut self) {
+        for entry in disassemble(&self.mem).entries() {
+            if let Entry::Code { addr, inst } = entry {
+                self.ops[*addr] = Some(Op::from(inst));
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            state: self.state,
+            mem: self.mem.clone(),
+            pos: self.pos,
+            relative_base: self.relative_base,
+            inputs: self.inputs.iter().copied().collect(),
+        }
+    }
+
+    pub fn into_machine(self) -> Machine {
+        Machine::from(self.snapshot())
+    }
+
+    pub fn push_input(&mut self, val: Int) {
+        self.inputs.push_back(val);
+    }
+
+    /// Reads memory without growing it, treating unallocated cells as 0.
+    pub fn peek(&self, pos: usize) -> Int {
+        self.mem.get(pos).copied().unwrap_or(0)
+    }
+
+    pub fn write(&mut self, pos: usize, val: Int) {
+        self.grow(pos);
+        self.mem[pos] = val;
+
+        for start in pos.saturating_sub(3)..=pos {
+            if let Some(op) = self.ops[start] {
+                if start + op.size > pos {
+                    self.ops[start] = None;
+                    self.invalidations += 1;
+                }
+            }
+        }
+    }
+
+    /// Same as `Machine::resume`.
+    pub fn resume(&mut self) -> Result<Status, Error> {
+        loop {
+            match self.step()? {
+                Status::Running => {}
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Same as `Machine::run_with_input`.
+    pub fn run_with_input<I>(&mut self, input: I) -> Result<Vec<Int>, Error>
+    where
+        I: IntoIterator<Item = Int>,
+    {
+        self.inputs.extend(input);
+
+        let mut outputs = Vec::new();
+        loop {
+            match self.resume()? {
+                Status::Output(val) => outputs.push(val),
+                Status::Halted => return Ok(outputs),
+                _ => {
+                    return Err(Error::InvalidInputError(
+                        "program needs more input".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Same as `Machine::step`.
+    pub fn step(&mut self) -> Result<Status, Error> {
+        if self.state == OperationState::Exited {
+            return Ok(Status::Halted);
+        }
+
+        let op = match self.ops.get(self.pos).copied().flatten() {
+            Some(op) => op,
+            None => self.decode()?,
+        };
+        let next = self.pos + op.size;
+
+        match op.opcode {
+            1 => {
+                let val = self.get(&op, 0) + self.get(&op, 1);
+                self.put(&op, 2, val);
+            }
+            2 => {
+                let val = self.get(&op, 0) * self.get(&op, 1);
+                self.put(&op, 2, val);
+            }
+            3 => match self.inputs.pop_front() {
+                Some(input) => self.put(&op, 0, input),
+                None => return Ok(Status::AwaitingInput),
+            },
+            4 => {
+                self.pos = next;
+                return Ok(Status::Output(self.get(&op, 0)));
+            }
+            5 | 6 => {
+                if (self.get(&op, 0) != 0) == (op.opcode == 5) {
+                    self.pos = self.get(&op, 1) as usize;
+                    return Ok(Status::Running);
+                }
+            }
+            7 => {
+                let val = (self.get(&op, 0) < self.get(&op, 1)) as Int;
+                self.put(&op, 2, val);
+            }
+            8 => {
+                let val = (self.get(&op, 0) == self.get(&op, 1)) as Int;
+                self.put(&op, 2, val);
+            }
+            9 => self.relative_base += self.get(&op, 0) as isize,
+            _ => {
+                self.pos = next;
+                self.state = OperationState::Exited;
+                return Ok(Status::Halted);
+            }
+        }
+
+        self.pos = next;
+        Ok(Status::Running)
+    }
+
+    fn decode(&mut self) -> Result<Op, Error> {
+        let mut pos = self.pos;
+        let inst = Instruction::parse(|| {
+            pos += 1;
+            self.peek(pos - 1)
+        })?;
+
+        let op = Op::from(&inst);
+        for pos in self.pos..self.pos + op.size {
+            self.grow(pos);
+        }
+        self.ops[self.pos] = Some(op);
+        Ok(op)
+    }
+
+    fn addr(&self, op: &Op, i: usize) -> usize {
+        match op.modes[i] {
+            ParameterMode::Relative => (self.relative_base + op.args[i] as isize) as usize,
+            _ => op.args[i] as usize,
+        }
+    }
+
+    fn get(&mut self, op: &Op, i: usize) -> Int {
+        match op.modes[i] {
+            ParameterMode::Immediate => op.args[i],
+            _ => {
+                let addr = self.addr(op, i);
+                self.grow(addr);
+                self.mem[addr]
+            }
+        }
+    }
+
+    fn put(&mut self, op: &Op, i: usize, val: Int) {
+        let addr = self.addr(op, i);
+        self.write(addr, val);
+    }
+
+    /// Grows memory the same way `Machine` does, so snapshots match.
+    fn grow(&mut self, pos: usize) {
+        if pos >= self.mem.len() {
+            let len = (pos * 2).max(self.mem.len() * 2);
+            self.mem.resize(len, 0);
+            self.ops.resize(len, None);
+        }
+    }
+}
+
+impl From<Snapshot> for Engine {
+    fn from(snapshot: Snapshot) -> Self {
+        Self {
+            state: snapshot.state,
+            ops: vec![None; snapshot.mem.len()],
+            mem: snapshot.mem,
+            pos: snapshot.pos,
+            relative_base: snapshot.relative_base,
+            inputs: snapshot.inputs.into(),
+            invalidations: 0,
+        }
+    }
+}
+
+impl From<&Machine> for Engine {
+    fn from(machine: &Machine) -> Self {
+        let mut engine = Self::from(machine.snapshot());
+        engine.compile();
+        engine
+    }
+}
+
+/// Runs `program` on both a `Machine` and an `Engine`, checking they end up
+/// in exactly the same state.
+#[allow(dead_code)]
+fn run_both(program: &str, input: Vec<Int>) -> Vec<Int> {
+    let mut machine = Machine::new();
+    machine.init(program).unwrap();
+    let mut engine = Engine::new(program).unwrap();
+
+    let expected = machine.run_with_input(input.clone()).unwrap();
+    assert_eq!(engine.run_with_input(input).unwrap(), expected);
+    assert_eq!(engine.snapshot(), machine.snapshot());
+    expected
+}
+
+#[test]
+fn matches_the_interpreter() {
+    // Day 2
+    for program in [
+        "1,0,0,0,99",
+        "2,3,0,3,99",
+        "2,4,4,5,99,0",
+        "1,1,1,4,99,5,6,0,99",
+    ] {
+        run_both(program, vec![]);
+    }
+
+    // Day 5
+    let larger = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,\
+                  125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
+    for input in 6..11 {
+        run_both(larger, vec![input]);
+    }
+    assert_eq!(
+        run_both("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", vec![0]),
+        vec![0]
+    );
+
+    // Day 9
+    let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+    assert_eq!(
+        run_both(quine, vec![]),
+        IntVec::from_str(quine).unwrap().into_inner()
+    );
+    assert_eq!(
+        run_both("104,1125899906842624,99", vec![]),
+        vec![1125899906842624]
+    );
+}
+
+#[test]
+fn evicts_self_modified_code() {
+    // Day 5's example multiplies its own last cell into a halt instruction.
+    let mut engine = Engine::new("1002,4,3,4,33").unwrap();
+    assert_eq!(engine.run_with_input(vec![]).unwrap(), vec![]);
+    assert_eq!(engine.peek(4), 99);
+
+    // Outputs [12] and then rewrites that output instruction to output 5
+    // instead, through the cached copy of the program.
+    let program = "4,13,1101,0,5,1,1101,104,0,0,1105,1,0,7";
+    let mut engine = Engine::new(program).unwrap();
+    let mut outputs = Vec::new();
+    for _ in 0..3 {
+        if let Status::Output(val) = engine.resume().unwrap() {
+            outputs.push(val);
+        }
+    }
+    assert_eq!(outputs, vec![7, 5, 5]);
+    assert_eq!(engine.invalidations(), 2);
+}
@@ -0,0 +1,360 @@
+use crate::intcode_computer::{Int, OperationState, Snapshot};
+
+use std::collections::{BTreeMap, VecDeque};
+
+/// Most checkpoints kept at once. When there are more, the oldest is
+/// dropped along with the inputs only it needed.
+const MAX_CHECKPOINTS: usize = 64;
+
+/// What's needed to undo a single executed instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Where the instruction started.
+    pub pos: usize,
+    pub relative_base: isize,
+    pub state: OperationState,
+    pub mem_len: usize,
+    /// The inputs it consumed, in order. Only custom instructions can
+    /// consume more than one.
+    pub inputs: Vec<Int>,
+    /// The cells it wrote and the values they held before, in order.
+    pub writes: Vec<(usize, Int)>,
+}
+
+/// A reversible execution log for a `Machine`.
+///
+/// Undo records are only kept for the most recent `window` instructions.
+/// Further back, the machine is restored from a periodic checkpoint and
+/// re-executed with the inputs it consumed at the time. Only the latest
+/// `MAX_CHECKPOINTS` checkpoints are kept, so memory use stays bounded
+/// however long the program runs, at the cost of not being able to go back
+/// past the oldest of them.
+#[derive(Debug, Clone)]
+pub struct History {
+    window: usize,
+    interval: u64,
+    step: u64,
+    records: VecDeque<Record>,
+    checkpoints: BTreeMap<u64, Snapshot>,
+    inputs: Vec<(u64, Int)>,
+    pending_inputs: Vec<Int>,
+    pending_writes: Vec<(usize, Int)>,
+}
+
+impl History {
+    /// Starts a history at `start`, keeping undo records for the last
+    /// `window` instructions and a checkpoint every `interval`.
+    pub fn new(window: usize, interval: u64, start: Snapshot) -> Self {
+        let mut checkpoints = BTreeMap::new();
+        checkpoints.insert(0, start);
+
+        Self {
+            window,
+            interval: interval.max(1),
+            step: 0,
+            records: VecDeque::new(),
+            checkpoints,
+            inputs: Vec::new(),
+            pending_inputs: Vec::new(),
+            pending_writes: Vec::new(),
+        }
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Number of instructions executed since the history started.
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    /// The earliest step that can be undone without re-executing.
+    pub fn oldest_record(&self) -> u64 {
+        self.step - self.records.len() as u64
+    }
+
+    /// The earliest step that can be gone back to.
+    pub fn oldest_checkpoint(&self) -> u64 {
+        *self
+            .checkpoints
+            .keys()
+            .next()
+            .expect("history has no checkpoints")
+    }
+
+    /// Undo records along with the step each one undoes, oldest first.
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = (u64, &Record)> {
+        let oldest = self.oldest_record();
+        self.records
+            .iter()
+            .enumerate()
+            .map(move |(i, record)| (oldest + i as u64, record))
+    }
+
+    pub fn checkpoints(&self) -> impl Iterator<Item = &u64> {
+        self.checkpoints.keys()
+    }
+
+    pub(crate) fn record_input(&mut self, val: Int) {
+        self.pending_inputs.push(val);
+    }
+
+    pub(crate) fn record_write(&mut self, addr: usize, old: Int) {
+        self.pending_writes.push((addr, old));
+    }
+
+    /// Logs an executed instruction, returning whether a checkpoint is due.
+    pub(crate) fn push(
+        &mut self,
+        pos: usize,
+        relative_base: isize,
+        state: OperationState,
+        mem_len: usize,
+    ) -> bool {
+        let inputs = std::mem::take(&mut self.pending_inputs);
+        self.inputs
+            .extend(inputs.iter().map(|val| (self.step, *val)));
+
+        self.records.push_back(Record {
+            pos,
+            relative_base,
+            state,
+            mem_len,
+            inputs,
+            writes: std::mem::take(&mut self.pending_writes),
+        });
+        if self.records.len() > self.window {
+            self.records.pop_front();
+        }
+
+        self.step += 1;
+        self.step.is_multiple_of(self.interval)
+    }
+
+    pub(crate) fn add_checkpoint(&mut self, snapshot: Snapshot) {
+        self.checkpoints.insert(self.step, snapshot);
+
+        if self.checkpoints.len() > MAX_CHECKPOINTS {
+            self.checkpoints.pop_first();
+            let oldest = self.oldest_checkpoint();
+            self.inputs.retain(|(step, _)| *step >= oldest);
+        }
+    }
+
+    /// Takes back the most recent record, if it's still kept.
+    pub(crate) fn pop(&mut self) -> Option<Record> {
+        let record = self.records.pop_back()?;
+        self.step -= 1;
+        self.inputs
+            .truncate(self.inputs.len() - record.inputs.len());
+        self.checkpoints.split_off(&(self.step + 1));
+        Some(record)
+    }
+
+    /// The latest checkpoint at or before `step`.
+    pub(crate) fn checkpoint_before(&self, step: u64) -> (u64, &Snapshot) {
+        self.checkpoints
+            .range(..=step)
+            .next_back()
+            .map(|(step, snapshot)| (*step, snapshot))
+            .expect("history has no initial checkpoint")
+    }
+
+    /// Inputs consumed by the instructions from step `from` up to `to`.
+    pub(crate) fn inputs_between(&self, from: u64, to: u64) -> Vec<Int> {
+        self.inputs
+            .iter()
+            .filter(|(step, _)| (from..to).contains(step))
+            .map(|(_, val)| *val)
+            .collect()
+    }
+
+    /// Forgets everything after `step`, which must have a checkpoint.
+    pub(crate) fn truncate(&mut self, step: u64) {
+        self.step = step;
+        self.records.clear();
+        self.checkpoints.split_off(&(step + 1));
+        self.inputs.retain(|(s, _)| *s < step);
+        self.pending_inputs.clear();
+        self.pending_writes.clear();
+    }
+}
+
+#[allow(dead_code)]
+fn countdown() -> crate::intcode_computer::Machine {
+    use crate::intcode_computer::Machine;
+
+    let mut machine = Machine::new();
+    // Reads a count into [13], then outputs and decrements it until it's 0
+    machine
+        .init("3,13,4,13,1001,13,-1,13,1005,13,2,99,0,0")
+        .unwrap();
+    machine
+}
+
+#[test]
+fn steps_back_to_earlier_states() {
+    let mut machine = countdown();
+    machine.enable_history(1000, 1000);
+    machine.push_input(3);
+
+    let mut snapshots = vec![machine.snapshot()];
+    while *machine.state() != OperationState::Exited {
+        machine.step().unwrap();
+        snapshots.push(machine.snapshot());
+    }
+    assert_eq!(machine.history().unwrap().step(), 11);
+
+    while machine.step_back().unwrap() {
+        let step = machine.history().unwrap().step() as usize;
+        assert_eq!(machine.snapshot(), snapshots[step]);
+    }
+    assert_eq!(machine.snapshot(), snapshots[0]);
+    assert_eq!(
+        machine.resume().unwrap(),
+        crate::intcode_computer::Status::Output(3)
+    );
+}
+
+#[test]
+fn rewinds_past_the_window_from_checkpoints() {
+    let mut machine = countdown();
+    machine.enable_history(4, 3);
+    machine.push_input(20);
+
+    let mut snapshots = vec![machine.snapshot()];
+    while *machine.state() != OperationState::Exited {
+        machine.step().unwrap();
+        snapshots.push(machine.snapshot());
+    }
+    let end = machine.history().unwrap().step();
+    assert_eq!(machine.history().unwrap().oldest_record(), end - 4);
+
+    machine.rewind_to(5).unwrap();
+    assert_eq!(machine.snapshot(), snapshots[5]);
+    machine.rewind_to(0).unwrap();
+    assert_eq!(machine.snapshot(), snapshots[0]);
+
+    // The input is given back, so running again ends up in the same place.
+    assert_eq!(machine.run_with_input(vec![]).unwrap().len(), 20);
+    assert_eq!(machine.snapshot(), snapshots[end as usize]);
+}
+
+#[test]
+fn forgets_steps_before_the_oldest_checkpoint() {
+    let mut machine = countdown();
+    machine.enable_history(2, 1);
+    machine.push_input(40);
+
+    let mut snapshots = vec![machine.snapshot()];
+    while *machine.state() != OperationState::Exited {
+        machine.step().unwrap();
+        snapshots.push(machine.snapshot());
+    }
+    let history = machine.history().unwrap();
+    let (end, oldest) = (history.step(), history.oldest_checkpoint());
+    assert_eq!(history.checkpoints().count(), MAX_CHECKPOINTS);
+    assert_eq!(oldest, end - MAX_CHECKPOINTS as u64 + 1);
+    assert!(history.inputs.is_empty());
+
+    assert!(matches!(
+        machine.rewind_to(oldest - 1),
+        Err(crate::intcode_computer::Error::HistoryError(_))
+    ));
+    assert_eq!(machine.last_write(12).unwrap(), None);
+    while machine.step_back().unwrap() {}
+    assert_eq!(machine.history().unwrap().step(), oldest);
+    assert_eq!(machine.snapshot(), snapshots[oldest as usize]);
+}
+
+#[test]
+fn finds_who_wrote_a_cell() {
+    for window in [1, 3] {
+        let mut machine = countdown();
+        machine.enable_history(window, 100);
+        machine.run_with_input(vec![2]).unwrap();
+
+        assert_eq!(machine.last_write(13).unwrap(), Some((5, 4)));
+        assert_eq!(machine.last_write(12).unwrap(), None);
+        machine.rewind_to(5).unwrap();
+        assert_eq!(machine.pos(), 4);
+        assert_eq!(machine.peek(13), 1);
+    }
+
+    // Only the last instruction has an undo record, so finding the input
+    // instruction means replaying from the start.
+    let mut machine = countdown();
+    machine.enable_history(1, 100);
+    machine.push_input(2);
+    machine.step().unwrap();
+    machine.step().unwrap();
+    assert_eq!(machine.last_write(13).unwrap(), Some((0, 0)));
+}
+
+#[test]
+fn finds_writes_from_before_the_window() {
+    use crate::intcode_computer::{Machine, Status, StrictMode};
+    use crate::intcode_opcodes::Opcodes;
+
+    // Stores one input in [20] and counts the other down to 0 in [21],
+    // using a custom decrement instruction
+    let mut machine = Machine::new();
+    machine.init("3,20,3,21,42,21,1005,21,4,99").unwrap();
+    let mut opcodes = Opcodes::new();
+    opcodes.register(42, "dec", 1, |ops| {
+        let val = ops.get(0) - 1;
+        ops.set(0, val);
+        Ok(Status::Running)
+    });
+    machine.set_opcodes(opcodes);
+    machine.enable_strict(StrictMode { max_memory: 32 });
+    machine.enable_history(2, 2);
+    machine.run_with_input(vec![9, 3]).unwrap();
+
+    let history = machine.history().unwrap();
+    assert_eq!(history.oldest_record(), 7);
+    assert!(history.checkpoints().count() > 1);
+
+    // Both writes are older than the undo records and a few checkpoints
+    assert_eq!(machine.last_write(21).unwrap(), Some((6, 4)));
+    assert_eq!(machine.last_write(20).unwrap(), Some((0, 0)));
+    assert_eq!(machine.last_write(30).unwrap(), None);
+}
+
+#[test]
+fn undoes_instructions_that_write_more_than_once() {
+    use crate::intcode_computer::{Machine, Status};
+    use crate::intcode_opcodes::Opcodes;
+
+    // Reads two inputs into [5] and [6] with one custom instruction
+    let mut machine = Machine::new();
+    machine.init("43,5,6,99,0,0,0").unwrap();
+    let mut opcodes = Opcodes::new();
+    opcodes.register(43, "in2", 2, |ops| {
+        for i in 0..2 {
+            let val = ops.input().unwrap();
+            ops.set(i, val);
+        }
+        Ok(Status::Running)
+    });
+    machine.set_opcodes(opcodes);
+    machine.enable_history(10, 10);
+    machine.push_input(7);
+    machine.push_input(8);
+    let start = machine.snapshot();
+
+    machine.step().unwrap();
+    assert_eq!((machine.peek(5), machine.peek(6)), (7, 8));
+    assert_eq!(machine.last_write(5).unwrap(), Some((0, 0)));
+    assert_eq!(machine.last_write(6).unwrap(), Some((0, 0)));
+
+    assert!(machine.step_back().unwrap());
+    assert_eq!(machine.snapshot(), start);
+    machine.step().unwrap();
+    assert_eq!((machine.peek(5), machine.peek(6)), (7, 8));
+}
@@ -0,0 +1,426 @@
+use async_std::channel::{Receiver, Sender};
+use futures::future::BoxFuture;
+
+use crate::grid::{Coord, Grid};
+use crate::intcode_computer::{Error, Int};
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Stdin, Stdout, Write};
+use std::path::Path;
+
+/// Somewhere an Intcode program's input instructions read from.
+pub trait IntcodeInput: Send {
+    fn recv(&mut self) -> BoxFuture<'_, Result<Int, Error>>;
+}
+
+/// Somewhere an Intcode program's output instructions write to.
+pub trait IntcodeOutput: Send {
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>>;
+}
+
+impl IntcodeInput for Receiver<Int> {
+    fn recv(&mut self) -> BoxFuture<'_, Result<Int, Error>> {
+        Box::pin(async move {
+            Receiver::recv(self)
+                .await
+                .map_err(|err| Error::InvalidInputError(err.to_string()))
+        })
+    }
+}
+
+impl IntcodeInput for &Receiver<Int> {
+    fn recv(&mut self) -> BoxFuture<'_, Result<Int, Error>> {
+        Box::pin(async move {
+            Receiver::recv(*self)
+                .await
+                .map_err(|err| Error::InvalidInputError(err.to_string()))
+        })
+    }
+}
+
+impl IntcodeOutput for Sender<Int> {
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            Sender::send(self, val)
+                .await
+                .map_err(|err| Error::InvalidOutputError(err.to_string()))
+        })
+    }
+}
+
+impl IntcodeOutput for &Sender<Int> {
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            Sender::send(*self, val)
+                .await
+                .map_err(|err| Error::InvalidOutputError(err.to_string()))
+        })
+    }
+}
+
+impl<T: IntcodeInput + ?Sized> IntcodeInput for &mut T {
+    fn recv(&mut self) -> BoxFuture<'_, Result<Int, Error>> {
+        (**self).recv()
+    }
+}
+
+impl<T: IntcodeOutput + ?Sized> IntcodeOutput for &mut T {
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>> {
+        (**self).send(val)
+    }
+}
+
+impl<T: IntcodeInput + ?Sized> IntcodeInput for Box<T> {
+    fn recv(&mut self) -> BoxFuture<'_, Result<Int, Error>> {
+        (**self).recv()
+    }
+}
+
+impl<T: IntcodeOutput + ?Sized> IntcodeOutput for Box<T> {
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>> {
+        (**self).send(val)
+    }
+}
+
+/// An in-memory queue of inputs. Running out is an error.
+impl IntcodeInput for VecDeque<Int> {
+    fn recv(&mut self) -> BoxFuture<'_, Result<Int, Error>> {
+        let val = self
+            .pop_front()
+            .ok_or_else(|| Error::InvalidInputError("input queue is empty".to_string()));
+        Box::pin(async move { val })
+    }
+}
+
+/// Collects every output in memory.
+impl IntcodeOutput for Vec<Int> {
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>> {
+        self.push(val);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Input pulled from a closure whenever the program asks for it.
+/// Returning `None` ends the input with an error.
+pub struct FnInput<F>(pub F);
+
+impl<F> IntcodeInput for FnInput<F>
+where
+    F: FnMut() -> Option<Int> + Send,
+{
+    fn recv(&mut self) -> BoxFuture<'_, Result<Int, Error>> {
+        let val =
+            (self.0)().ok_or_else(|| Error::InvalidInputError("input closure ran out".to_string()));
+        Box::pin(async move { val })
+    }
+}
+
+/// Output handed to a closure as it's produced.
+pub struct FnOutput<F>(pub F);
+
+impl<F> IntcodeOutput for FnOutput<F>
+where
+    F: FnMut(Int) + Send,
+{
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>> {
+        (self.0)(val);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Groups output into fixed-size chunks, like the `(x, y, tile)` triples
+/// of the arcade cabinet, and hands each complete chunk to a closure.
+pub struct ChunkedOutput<F> {
+    size: usize,
+    chunk: Vec<Int>,
+    f: F,
+}
+
+impl<F> ChunkedOutput<F>
+where
+    F: FnMut(&[Int]) + Send,
+{
+    pub fn new(size: usize, f: F) -> Self {
+        Self {
+            size,
+            chunk: Vec::with_capacity(size),
+            f,
+        }
+    }
+}
+
+impl<F> IntcodeOutput for ChunkedOutput<F>
+where
+    F: FnMut(&[Int]) + Send,
+{
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>> {
+        self.chunk.push(val);
+        if self.chunk.len() == self.size {
+            (self.f)(&self.chunk);
+            self.chunk.clear();
+        }
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Feeds lines of text to an ASCII-speaking program, one character per
+/// input instruction, each line ending with a newline. Reading blocks, so
+/// this is meant for interactive or scripted consoles.
+pub struct AsciiInput<R> {
+    reader: R,
+    pending: VecDeque<Int>,
+}
+
+impl<R: BufRead + Send> AsciiInput<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl AsciiInput<BufReader<Stdin>> {
+    pub fn stdin() -> Self {
+        Self::new(BufReader::new(io::stdin()))
+    }
+}
+
+impl<R: BufRead + Send> IntcodeInput for AsciiInput<R> {
+    fn recv(&mut self) -> BoxFuture<'_, Result<Int, Error>> {
+        let val = loop {
+            if let Some(val) = self.pending.pop_front() {
+                break Ok(val);
+            }
+
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => break Err(Error::InvalidInputError("end of input".to_string())),
+                Ok(_) => {
+                    let line = line.trim_end_matches(['\r', '\n']);
+                    self.pending.extend(line.bytes().map(Int::from));
+                    self.pending.push_back(b'\n' as Int);
+                }
+                Err(err) => break Err(Error::InvalidInputError(err.to_string())),
+            }
+        };
+        Box::pin(async move { val })
+    }
+}
+
+/// Writes ASCII output as text. Anything outside the ASCII range, like a
+/// puzzle answer, is written as a number on its own line.
+pub struct AsciiOutput<W> {
+    writer: W,
+}
+
+impl<W: Write + Send> AsciiOutput<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl AsciiOutput<Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write + Send> IntcodeOutput for AsciiOutput<W> {
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>> {
+        let result = match u8::try_from(val) {
+            Ok(c) if c.is_ascii() => self.writer.write_all(&[c]),
+            _ => writeln!(self.writer, "{}", val),
+        }
+        .and_then(|_| {
+            if val == b'\n' as Int {
+                self.writer.flush()
+            } else {
+                Ok(())
+            }
+        })
+        .map_err(|err| Error::InvalidOutputError(err.to_string()));
+        Box::pin(async move { result })
+    }
+}
+
+/// Draws ASCII output into a grid, one row per line, like the camera
+/// output of the vacuum robot.
+#[derive(Debug, Default)]
+pub struct AsciiGridOutput {
+    pub grid: Grid<char>,
+    x: i64,
+    y: i64,
+}
+
+impl AsciiGridOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IntcodeOutput for AsciiGridOutput {
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>> {
+        let result = match u8::try_from(val) {
+            Ok(b'\n') => {
+                self.x = 0;
+                self.y += 1;
+                Ok(())
+            }
+            Ok(c) if c.is_ascii() => {
+                self.grid.insert(Coord(self.x, self.y), c.into());
+                self.x += 1;
+                Ok(())
+            }
+            _ => Err(Error::InvalidOutputError(format!(
+                "{} is not an ASCII character",
+                val
+            ))),
+        };
+        Box::pin(async move { result })
+    }
+}
+
+/// Passes values through to another input or output, writing each one to
+/// a log in the same comma-separated format as `IntVec`, so recordings can
+/// be loaded back with `IntVec::from_str`.
+pub struct Recorder<T, W> {
+    inner: T,
+    log: W,
+    count: usize,
+}
+
+impl<T, W: Write + Send> Recorder<T, W> {
+    pub fn new(inner: T, log: W) -> Self {
+        Self {
+            inner,
+            log,
+            count: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> (T, W) {
+        (self.inner, self.log)
+    }
+
+    fn record(&mut self, val: Int) -> io::Result<()> {
+        if self.count > 0 {
+            write!(self.log, ",")?;
+        }
+        self.count += 1;
+        write!(self.log, "{}", val)?;
+        self.log.flush()
+    }
+}
+
+impl<T> Recorder<T, BufWriter<File>> {
+    pub fn to_file<P: AsRef<Path>>(inner: T, path: P) -> io::Result<Self> {
+        Ok(Self {
+            inner,
+            log: BufWriter::new(File::create(path)?),
+            count: 0,
+        })
+    }
+}
+
+impl<T: IntcodeInput, W: Write + Send> IntcodeInput for Recorder<T, W> {
+    fn recv(&mut self) -> BoxFuture<'_, Result<Int, Error>> {
+        Box::pin(async move {
+            let val = self.inner.recv().await?;
+            self.record(val)
+                .map_err(|err| Error::InvalidInputError(err.to_string()))?;
+            Ok(val)
+        })
+    }
+}
+
+impl<T: IntcodeOutput, W: Write + Send> IntcodeOutput for Recorder<T, W> {
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            self.record(val)
+                .map_err(|err| Error::InvalidOutputError(err.to_string()))?;
+            self.inner.send(val).await
+        })
+    }
+}
+
+#[test]
+fn adapters_work_with_processes() {
+    use crate::intcode_computer::{IntcodeProcess, Machine};
+    use async_std::task;
+
+    // Echoes its input until it reads a 0
+    let mut machine = Machine::new();
+    machine.init("3,9,4,9,1005,9,0,99,0,0").unwrap();
+
+    let mut outputs = Vec::new();
+    let process = IntcodeProcess::new(
+        machine.clone(),
+        VecDeque::from(vec![1, 2, 3, 0]),
+        &mut outputs,
+    );
+    task::block_on(process.run()).unwrap();
+    assert_eq!(outputs, vec![1, 2, 3, 0]);
+
+    let mut next = 5;
+    let mut chunks = Vec::new();
+    let process = IntcodeProcess::new(
+        machine.clone(),
+        FnInput(|| {
+            next -= 1;
+            Some(next)
+        }),
+        ChunkedOutput::new(2, |chunk: &[Int]| chunks.push(chunk.to_vec())),
+    );
+    task::block_on(process.run()).unwrap();
+    assert_eq!(chunks, vec![vec![4, 3], vec![2, 1]]);
+
+    let mut log = Vec::new();
+    let process = IntcodeProcess::new(
+        machine,
+        Recorder::new(VecDeque::from(vec![7, 0]), &mut log),
+        Vec::new(),
+    );
+    task::block_on(process.run()).unwrap();
+    assert_eq!(String::from_utf8(log).unwrap(), "7,0");
+}
+
+#[test]
+fn ascii_adapters_translate_text() {
+    use crate::intcode_computer::{IntcodeProcess, Machine};
+    use async_std::task;
+
+    // Echoes its input, then prints 1000
+    let mut machine = Machine::new();
+    machine
+        .init("3,100,4,100,1008,100,10,101,1006,101,0,104,1000,99")
+        .unwrap();
+
+    let mut output = AsciiOutput::new(Vec::new());
+    task::block_on(
+        IntcodeProcess::new(
+            machine.clone(),
+            AsciiInput::new("hi\nthere\n".as_bytes()),
+            &mut output,
+        )
+        .run(),
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(output.into_inner()).unwrap(),
+        "hi\n1000\n"
+    );
+
+    let mut grid = AsciiGridOutput::new();
+    task::block_on(
+        IntcodeProcess::new(machine, AsciiInput::new("#.\n".as_bytes()), &mut grid).run(),
+    )
+    .unwrap_err();
+    assert_eq!(grid.grid.get(&Coord(0, 0)), Some(&'#'));
+    assert_eq!(grid.grid.get(&Coord(1, 0)), Some(&'.'));
+}
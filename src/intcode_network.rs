@@ -0,0 +1,367 @@
+use crate::intcode_computer::{Error, Int, Machine, OperationState, Status};
+
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+
+/// Address the NAT listens on.
+pub const NAT_ADDRESS: Int = 255;
+
+/// Value given to a machine that asks for input while its queue is empty.
+const NO_PACKET: Int = -1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub dest: Int,
+    pub x: Int,
+    pub y: Int,
+}
+
+/// Watches traffic on a `Network`. Breaking out of any hook stops the
+/// network and hands the value back from `Network::run`.
+pub trait Monitor {
+    /// Called for every packet a machine sends, before it's routed.
+    fn sent(&mut self, _from: usize, _packet: &Packet) -> ControlFlow<Int> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called with packets addressed outside the network, which would
+    /// otherwise be dropped.
+    fn unroutable(&mut self, _packet: Packet) -> ControlFlow<Int> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called when every machine is polling an empty queue and nothing was
+    /// sent for a whole round. Continuing with a packet delivers it,
+    /// continuing with `None` leaves the network deadlocked.
+    fn idle(&mut self) -> ControlFlow<Int, Option<Packet>> {
+        ControlFlow::Continue(None)
+    }
+}
+
+/// A monitor that only lets the network run until it deadlocks.
+impl Monitor for () {}
+
+/// The NAT from day 23. It remembers the last packet sent to
+/// `NAT_ADDRESS` and sends it to address 0 whenever the network is idle,
+/// stopping with the `y` value once it sends the same one twice in a row.
+#[derive(Debug, Default)]
+pub struct Nat {
+    pub first: Option<Packet>,
+    pub last: Option<Packet>,
+    delivered_y: Option<Int>,
+}
+
+impl Nat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Monitor for Nat {
+    fn unroutable(&mut self, packet: Packet) -> ControlFlow<Int> {
+        if packet.dest == NAT_ADDRESS {
+            self.first.get_or_insert(packet);
+            self.last = Some(packet);
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn idle(&mut self) -> ControlFlow<Int, Option<Packet>> {
+        let packet = match self.last {
+            Some(packet) => packet,
+            None => return ControlFlow::Continue(None),
+        };
+        if self.delivered_y == Some(packet.y) {
+            return ControlFlow::Break(packet.y);
+        }
+
+        self.delivered_y = Some(packet.y);
+        ControlFlow::Continue(Some(Packet { dest: 0, ..packet }))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    machine: Machine,
+    queue: VecDeque<(Int, Int)>,
+    output: Vec<Int>,
+}
+
+/// A packet-switched network of machines all running the same program.
+/// Each machine is first given its address as input, then reads `x, y`
+/// pairs from its queue, or -1 when there's nothing waiting. Machines send
+/// packets by outputting `dest, x, y`.
+///
+/// Machines run round-robin, each until it next asks for input, so runs
+/// are deterministic.
+#[derive(Debug, Clone)]
+pub struct Network {
+    nodes: Vec<Node>,
+}
+
+impl Network {
+    pub fn new(program: &str, size: usize) -> Result<Self, Error> {
+        let mut machine = Machine::new();
+        machine.init(program)?;
+        Ok(Self::from_machine(&machine, size))
+    }
+
+    pub fn from_machine(machine: &Machine, size: usize) -> Self {
+        let nodes = (0..size)
+            .map(|addr| {
+                let mut machine = machine.clone();
+                machine.push_input(addr as Int);
+                Node {
+                    machine,
+                    queue: VecDeque::new(),
+                    output: Vec::new(),
+                }
+            })
+            .collect();
+
+        Self { nodes }
+    }
+
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn machine(&self, addr: usize) -> &Machine {
+        &self.nodes[addr].machine
+    }
+
+    /// Queues a packet for delivery, returning false if there's no machine
+    /// at its address.
+    pub fn send(&mut self, packet: Packet) -> bool {
+        match self.node_mut(packet.dest) {
+            Some(node) => {
+                node.queue.push_back((packet.x, packet.y));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Runs until the monitor stops the network, returning the value it
+    /// stopped with, or `None` if the network deadlocked or every machine
+    /// halted.
+    pub fn run<M: Monitor + ?Sized>(&mut self, monitor: &mut M) -> Result<Option<Int>, Error> {
+        loop {
+            if let ControlFlow::Break(val) = self.round(monitor)? {
+                return Ok(val);
+            }
+            if self
+                .nodes
+                .iter()
+                .all(|node| *node.machine.state() == OperationState::Exited)
+            {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Gives every machine one turn, then checks whether the network has
+    /// gone idle. Breaks with `None` on deadlock.
+    fn round<M: Monitor + ?Sized>(
+        &mut self,
+        monitor: &mut M,
+    ) -> Result<ControlFlow<Option<Int>>, Error> {
+        let mut busy = false;
+        for addr in 0..self.nodes.len() {
+            let mut sent = Vec::new();
+            busy |= self.turn(addr, &mut sent)?;
+
+            for packet in sent {
+                busy = true;
+                if let ControlFlow::Break(val) = monitor.sent(addr, &packet) {
+                    return Ok(ControlFlow::Break(Some(val)));
+                }
+                if let ControlFlow::Break(val) = self.route(packet, monitor) {
+                    return Ok(ControlFlow::Break(Some(val)));
+                }
+            }
+        }
+
+        if busy {
+            return Ok(ControlFlow::Continue(()));
+        }
+
+        Ok(match monitor.idle() {
+            ControlFlow::Break(val) => ControlFlow::Break(Some(val)),
+            ControlFlow::Continue(Some(packet)) => self.route(packet, monitor).map_break(Some),
+            ControlFlow::Continue(None) => ControlFlow::Break(None),
+        })
+    }
+
+    fn route<M: Monitor + ?Sized>(&mut self, packet: Packet, monitor: &mut M) -> ControlFlow<Int> {
+        if self.send(packet) {
+            ControlFlow::Continue(())
+        } else {
+            monitor.unroutable(packet)
+        }
+    }
+
+    /// Runs a machine until it asks for input a second time, returning
+    /// whether it received a packet and collecting what it sent.
+    fn turn(&mut self, addr: usize, sent: &mut Vec<Packet>) -> Result<bool, Error> {
+        let node = &mut self.nodes[addr];
+        let mut received = false;
+        let mut fed = false;
+        loop {
+            match node.machine.resume()? {
+                Status::Output(val) => {
+                    node.output.push(val);
+                    if let [dest, x, y] = node.output[..] {
+                        sent.push(Packet { dest, x, y });
+                        node.output.clear();
+                    }
+                }
+                Status::AwaitingInput if !fed => {
+                    fed = true;
+                    match node.queue.pop_front() {
+                        Some((x, y)) => {
+                            received = true;
+                            node.machine.push_input(x);
+                            node.machine.push_input(y);
+                        }
+                        None => node.machine.push_input(NO_PACKET),
+                    }
+                }
+                Status::AwaitingInput | Status::Halted | Status::Running => break,
+            }
+        }
+
+        Ok(received)
+    }
+
+    fn node_mut(&mut self, addr: Int) -> Option<&mut Node> {
+        usize::try_from(addr)
+            .ok()
+            .and_then(move |addr| self.nodes.get_mut(addr))
+    }
+}
+
+/// Assembles a node that adds 1 to the `x` of every packet it receives and
+/// passes it on to the next address, with the last node sending to the NAT.
+#[allow(dead_code)]
+fn relay(size: usize) -> String {
+    use crate::intcode_assembler::assemble;
+
+    assemble(&format!(
+        "
+                in [addr]
+                add [addr], #1, [dest]
+                eq [dest], #{}, [flag]
+                jf [flag], #poll
+                add #{}, #0, [dest]
+        poll:   in [x]
+                eq [x], #-1, [flag]
+                jt [flag], #poll
+                in [y]
+                add [x], #1, [x]
+                out [dest]
+                out [x]
+                out [y]
+                jt #1, #poll
+        addr:   data 0
+        dest:   data 0
+        flag:   data 0
+        x:      data 0
+        y:      data 0
+        ",
+        size, NAT_ADDRESS
+    ))
+    .unwrap()
+    .to_string()
+}
+
+#[test]
+fn routes_packets_between_machines() {
+    #[derive(Default)]
+    struct Log(Vec<(usize, Packet)>);
+
+    impl Monitor for Log {
+        fn sent(&mut self, from: usize, packet: &Packet) -> ControlFlow<Int> {
+            self.0.push((from, *packet));
+            ControlFlow::Continue(())
+        }
+
+        fn unroutable(&mut self, packet: Packet) -> ControlFlow<Int> {
+            ControlFlow::Break(packet.x)
+        }
+    }
+
+    let mut network = Network::new(&relay(3), 3).unwrap();
+    assert!(network.send(Packet {
+        dest: 0,
+        x: 0,
+        y: 7
+    }));
+    assert!(!network.send(Packet {
+        dest: 3,
+        x: 0,
+        y: 7
+    }));
+
+    let mut log = Log::default();
+    assert_eq!(network.run(&mut log).unwrap(), Some(3));
+    assert_eq!(
+        log.0,
+        vec![
+            (
+                0,
+                Packet {
+                    dest: 1,
+                    x: 1,
+                    y: 7
+                }
+            ),
+            (
+                1,
+                Packet {
+                    dest: 2,
+                    x: 2,
+                    y: 7
+                }
+            ),
+            (
+                2,
+                Packet {
+                    dest: 255,
+                    x: 3,
+                    y: 7
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn nat_wakes_idle_networks() {
+    let mut network = Network::new(&relay(3), 3).unwrap();
+    assert_eq!(network.clone().run(&mut ()).unwrap(), None);
+
+    network.send(Packet {
+        dest: 0,
+        x: 0,
+        y: 7,
+    });
+    let mut nat = Nat::new();
+    assert_eq!(network.run(&mut nat).unwrap(), Some(7));
+    assert_eq!(
+        nat.first,
+        Some(Packet {
+            dest: 255,
+            x: 3,
+            y: 7
+        })
+    );
+    assert_eq!(
+        nat.last,
+        Some(Packet {
+            dest: 255,
+            x: 6,
+            y: 7
+        })
+    );
+}
@@ -0,0 +1,239 @@
+use crate::intcode_computer::{Error, Instruction, Int, Machine, Parameter, Status};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Carries out a custom instruction. Returning `Status::AwaitingInput`
+/// leaves the machine pointing at the instruction so it runs again once
+/// input is queued, and `Status::Halted` exits the program.
+pub type Handler = Arc<dyn Fn(&mut Operands) -> Result<Status, Error> + Send + Sync>;
+
+/// Most parameters a custom opcode can have. An instruction with every
+/// parameter's mode set has to fit in an `Int`.
+pub const MAX_ARITY: usize = 17;
+
+/// An opcode registered on top of the built in ones.
+#[derive(Clone)]
+pub struct CustomOpcode {
+    pub mnemonic: &'static str,
+    /// Number of parameters following the opcode.
+    pub arity: usize,
+    pub handler: Handler,
+}
+
+/// Extra opcodes a `Machine` understands, each decoded with its arity and
+/// parameter modes like a built in instruction and then handed to its
+/// handler to execute.
+#[derive(Clone, Default)]
+pub struct Opcodes {
+    ops: BTreeMap<Int, CustomOpcode>,
+}
+
+impl Opcodes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `opcode`, replacing any custom opcode already registered with
+    /// the same number. Panics if `opcode` is built in or doesn't fit in
+    /// an instruction's two opcode digits, or if `arity` is over
+    /// `MAX_ARITY`.
+    pub fn register<F>(
+        &mut self,
+        opcode: Int,
+        mnemonic: &'static str,
+        arity: usize,
+        handler: F,
+    ) -> &mut Self
+    where
+        F: Fn(&mut Operands) -> Result<Status, Error> + Send + Sync + 'static,
+    {
+        assert!(
+            (1..100).contains(&opcode),
+            "opcode {} isn't two digits",
+            opcode
+        );
+        assert!(
+            Instruction::parse_with(|| opcode, None).is_err(),
+            "opcode {} is built in",
+            opcode
+        );
+        assert!(
+            arity <= MAX_ARITY,
+            "opcode {} has more than {} parameters",
+            opcode,
+            MAX_ARITY
+        );
+
+        self.ops.insert(
+            opcode,
+            CustomOpcode {
+                mnemonic,
+                arity,
+                handler: Arc::new(handler),
+            },
+        );
+        self
+    }
+
+    pub fn get(&self, opcode: Int) -> Option<&CustomOpcode> {
+        self.ops.get(&opcode)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Int, &CustomOpcode)> {
+        self.ops.iter()
+    }
+}
+
+impl fmt::Debug for Opcodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.ops
+                    .iter()
+                    .map(|(opcode, op)| (opcode, format!("{}/{}", op.mnemonic, op.arity))),
+            )
+            .finish()
+    }
+}
+
+/// The parameters of a custom instruction being executed, along with the
+/// machine running it.
+///
+/// Writes and inputs go through the machine like a built in instruction's,
+/// so they show up in profiles and traces and can be undone.
+pub struct Operands<'a> {
+    machine: &'a mut Machine,
+    params: &'a [Parameter],
+}
+
+impl<'a> Operands<'a> {
+    pub(crate) fn new(machine: &'a mut Machine, params: &'a [Parameter]) -> Self {
+        Self { machine, params }
+    }
+
+    pub fn params(&self) -> &[Parameter] {
+        self.params
+    }
+
+    /// The value of parameter `i`, following its mode.
+    pub fn get(&mut self, i: usize) -> Int {
+        self.machine.param_value(&self.params[i])
+    }
+
+    /// Writes `val` to where parameter `i` points.
+    pub fn set(&mut self, i: usize, val: Int) {
+        self.machine.store_param(&self.params[i], val);
+    }
+
+    /// Continues execution at `addr` instead of the next instruction.
+    pub fn jump(&mut self, addr: usize) {
+        self.machine.jump(addr);
+    }
+
+    /// Consumes the next queued input, if there is one.
+    pub fn input(&mut self) -> Option<Int> {
+        self.machine.take_input()
+    }
+
+    pub fn machine(&self) -> &Machine {
+        self.machine
+    }
+}
+
+#[test]
+fn runs_custom_opcodes() {
+    use crate::intcode_disassembler::disassemble_with;
+
+    let mut opcodes = Opcodes::new();
+    opcodes
+        .register(20, "xor", 3, |ops| {
+            let val = ops.get(0) ^ ops.get(1);
+            ops.set(2, val);
+            Ok(Status::Running)
+        })
+        .register(21, "dbg", 1, |ops| Ok(Status::Output(-ops.get(0))))
+        .register(22, "acc", 1, |ops| match ops.input() {
+            Some(input) => {
+                let val = ops.get(0) + input;
+                ops.set(0, val);
+                Ok(Status::Running)
+            }
+            None => Ok(Status::AwaitingInput),
+        });
+
+    let mut machine = Machine::new();
+    machine
+        .init("1120,12,10,12,21,12,22,12,4,12,99,0,0")
+        .unwrap();
+    machine.set_opcodes(opcodes);
+    machine.enable_tracing(false);
+    assert_eq!(
+        machine.next_inst().unwrap().to_string(),
+        "xor #12, #10, [12]"
+    );
+    let disassembly = disassemble_with(machine.mem(), machine.opcodes().unwrap());
+    assert!(disassembly.to_string().contains("acc [var_12]"));
+
+    assert_eq!(machine.resume().unwrap(), Status::Output(-6));
+    assert_eq!(machine.resume().unwrap(), Status::AwaitingInput);
+    assert_eq!(machine.pos(), 6);
+    assert_eq!(machine.run_with_input(vec![3]).unwrap(), vec![9]);
+    let trace = machine.trace().unwrap();
+    assert_eq!(trace.outputs().collect::<Vec<Int>>(), vec![-6, 9]);
+
+    // Without the handlers the same program doesn't decode
+    let mut machine = Machine::new();
+    machine.init("1120,12,10,12,99").unwrap();
+    assert!(matches!(
+        machine.resume(),
+        Err(Error::OpcodeParseError(1120))
+    ));
+}
+
+#[test]
+fn decodes_custom_opcodes_with_every_mode_set() {
+    let mut opcodes = Opcodes::new();
+    opcodes.register(24, "wide", MAX_ARITY, |_| Ok(Status::Running));
+
+    let encoded = 2
+        * (0..MAX_ARITY as u32)
+            .map(|i| (10 as Int).pow(i))
+            .sum::<Int>()
+        * 100
+        + 24;
+    let mut words = vec![encoded].into_iter().chain(1..);
+    let inst = Instruction::parse_with(|| words.next().unwrap(), Some(&opcodes)).unwrap();
+    assert_eq!(inst.params().len(), MAX_ARITY);
+    assert_eq!(inst.encode()[0], encoded);
+}
+
+#[test]
+#[should_panic(expected = "more than 17 parameters")]
+fn rejects_custom_opcodes_with_too_many_parameters() {
+    Opcodes::new().register(24, "wider", MAX_ARITY + 1, |_| Ok(Status::Running));
+}
+
+#[test]
+fn custom_opcodes_output_after_reading_input() {
+    use crate::intcode_computer::IntcodeProcess;
+    use async_std::task;
+    use std::collections::VecDeque;
+
+    // Outputs its input doubled
+    let mut opcodes = Opcodes::new();
+    opcodes.register(23, "dbl", 0, |ops| match ops.input() {
+        Some(input) => Ok(Status::Output(input * 2)),
+        None => Ok(Status::AwaitingInput),
+    });
+
+    let mut machine = Machine::new();
+    machine.init("23,23,99").unwrap();
+    machine.set_opcodes(opcodes);
+
+    let mut outputs = Vec::new();
+    let process = IntcodeProcess::new(machine, VecDeque::from(vec![1, 2]), &mut outputs);
+    task::block_on(process.run()).unwrap();
+    assert_eq!(outputs, vec![2, 4]);
+}
@@ -0,0 +1,226 @@
+use async_std::channel::{bounded, Sender};
+use futures::future::{join_all, BoxFuture};
+
+use crate::intcode_computer::{Error, Int, IntcodeProcess, Machine, BUFFER_SIZE};
+use crate::intcode_io::IntcodeOutput;
+
+#[derive(Debug, Clone)]
+struct Node {
+    machine: Machine,
+    successors: Vec<usize>,
+}
+
+/// A set of machines wired output to input, such as day 7's amplifiers.
+/// Any shape works: chains, rings, fan-outs where every successor gets a
+/// copy of each output, and fan-ins where outputs are interleaved in
+/// whatever order they're produced.
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    nodes: Vec<Node>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Machines running copies of `machine` feeding into each other in
+    /// order, with one list of initial inputs per machine.
+    pub fn chain(machine: &Machine, initial_inputs: &[Vec<Int>]) -> Self {
+        let mut pipeline = Self::new();
+        for inputs in initial_inputs {
+            let node = pipeline.add(machine.clone(), inputs.iter().copied());
+            if node > 0 {
+                pipeline.connect(node - 1, node);
+            }
+        }
+        pipeline
+    }
+
+    /// Like `chain`, but with the last machine feeding back into the first.
+    pub fn ring(machine: &Machine, initial_inputs: &[Vec<Int>]) -> Self {
+        let mut pipeline = Self::chain(machine, initial_inputs);
+        if let Some(last) = pipeline.size().checked_sub(1) {
+            pipeline.connect(last, 0);
+        }
+        pipeline
+    }
+
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Adds a machine that reads `initial_inputs` before anything sent to
+    /// it by other machines, returning its index.
+    pub fn add<I: IntoIterator<Item = Int>>(
+        &mut self,
+        mut machine: Machine,
+        initial_inputs: I,
+    ) -> usize {
+        for input in initial_inputs {
+            machine.push_input(input);
+        }
+        self.nodes.push(Node {
+            machine,
+            successors: Vec::new(),
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Queues another initial input for a machine, like the starting
+    /// signal for the first amplifier.
+    pub fn push_input(&mut self, node: usize, val: Int) {
+        self.nodes[node].machine.push_input(val);
+    }
+
+    /// Sends everything `from` outputs to `to`.
+    pub fn connect(&mut self, from: usize, to: usize) -> &mut Self {
+        assert!(to < self.nodes.len(), "no node {} to connect to", to);
+        self.nodes[from].successors.push(to);
+        self
+    }
+
+    /// Runs every machine until it exits, returning everything each one
+    /// output, indexed by node. The outputs of nodes without successors
+    /// are the pipeline's results.
+    ///
+    /// A machine that needs more input after everything feeding it has
+    /// exited fails with `Error::InvalidInputError`. Outputs sent to a
+    /// machine that has already exited are dropped.
+    pub async fn run(self) -> Result<Vec<Vec<Int>>, Error> {
+        let (senders, receivers): (Vec<Sender<Int>>, Vec<_>) =
+            self.nodes.iter().map(|_| bounded(BUFFER_SIZE)).unzip();
+
+        let outputs: Vec<Fanout> = self
+            .nodes
+            .iter()
+            .map(|node| Fanout {
+                successors: node
+                    .successors
+                    .iter()
+                    .map(|i| senders[*i].clone())
+                    .collect(),
+                log: Vec::new(),
+            })
+            .collect();
+        // Only the successors' copies should keep a channel open.
+        drop(senders);
+
+        // Each process owns its fanout, so its senders close as soon as it
+        // exits and anything still waiting on it sees that.
+        let processes = self.nodes.into_iter().zip(receivers).zip(outputs).map(
+            |((node, input), mut output)| async move {
+                IntcodeProcess::new(node.machine, input, &mut output)
+                    .run()
+                    .await?;
+                Ok(output.log)
+            },
+        );
+
+        join_all(processes).await.into_iter().collect()
+    }
+}
+
+/// Copies each output to every successor, keeping a log of them all.
+struct Fanout {
+    successors: Vec<Sender<Int>>,
+    log: Vec<Int>,
+}
+
+impl IntcodeOutput for Fanout {
+    fn send(&mut self, val: Int) -> BoxFuture<'_, Result<(), Error>> {
+        self.log.push(val);
+        Box::pin(async move {
+            for successor in self.successors.iter() {
+                // A successor that has exited won't read it anyway.
+                let _ = successor.send(val).await;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[allow(dead_code)]
+fn machine(program: &str) -> Machine {
+    let mut machine = Machine::new();
+    machine.init(program).unwrap();
+    machine
+}
+
+#[test]
+fn chains_pass_outputs_along() {
+    use async_std::task;
+
+    // Day 7's first example
+    let amp = machine("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+    let phases = [4, 3, 2, 1, 0];
+    let inputs: Vec<Vec<Int>> = phases.iter().map(|phase| vec![*phase]).collect();
+    let mut pipeline = Pipeline::chain(&amp, &inputs);
+    pipeline.push_input(0, 0);
+
+    let outputs = task::block_on(pipeline.run()).unwrap();
+    assert_eq!(
+        outputs,
+        vec![vec![4], vec![43], vec![432], vec![4321], vec![43210]]
+    );
+}
+
+#[test]
+fn rings_feed_back_until_every_machine_exits() {
+    use async_std::task;
+
+    let amp = machine(
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+    );
+    let phases = [9, 8, 7, 6, 5];
+    let inputs: Vec<Vec<Int>> = phases.iter().map(|phase| vec![*phase]).collect();
+    let mut pipeline = Pipeline::ring(&amp, &inputs);
+    pipeline.push_input(0, 0);
+
+    let outputs = task::block_on(pipeline.run()).unwrap();
+    assert_eq!(outputs[4].last(), Some(&139629729));
+}
+
+#[test]
+fn fans_out_and_in() {
+    use async_std::task;
+
+    // One adds its two inputs, the other doubles its input
+    let add = machine("3,0,3,1,1,0,1,0,4,0,99");
+    let double = machine("3,0,102,2,0,0,4,0,99");
+
+    let mut pipeline = Pipeline::new();
+    let source = pipeline.add(double.clone(), [5]);
+    let left = pipeline.add(double.clone(), []);
+    let right = pipeline.add(double, []);
+    let sink = pipeline.add(add, []);
+    pipeline
+        .connect(source, left)
+        .connect(source, right)
+        .connect(left, sink)
+        .connect(right, sink);
+
+    let outputs = task::block_on(pipeline.run()).unwrap();
+    assert_eq!(outputs[sink], vec![40]);
+
+    // Nothing ever feeds the second input
+    let mut pipeline = Pipeline::new();
+    pipeline.add(machine("3,0,3,1,1,0,1,0,4,0,99"), [1]);
+    assert!(task::block_on(pipeline.run()).is_err());
+}
+
+#[test]
+fn starved_machines_fail_once_their_feeders_exit() {
+    use async_std::task;
+
+    // The first machine sends a single value, but the second wants two
+    let mut pipeline = Pipeline::new();
+    let source = pipeline.add(machine("3,0,4,0,99"), [7]);
+    let sink = pipeline.add(machine("3,0,3,1,1,0,1,0,4,0,99"), []);
+    pipeline.connect(source, sink);
+
+    assert!(matches!(
+        task::block_on(pipeline.run()),
+        Err(Error::InvalidInputError(_))
+    ));
+}
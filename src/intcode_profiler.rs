@@ -0,0 +1,201 @@
+use crate::intcode_disassembler::{Disassembly, Entry};
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+/// Number of addresses listed in the hottest instructions section of the
+/// text report.
+const HOTTEST: usize = 10;
+
+/// Execution counts and memory heatmaps collected by a profiling
+/// `Machine`. Instructions are counted by the address they start at.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub steps: u64,
+    pub executions: BTreeMap<usize, u64>,
+    pub opcodes: BTreeMap<&'static str, u64>,
+    pub reads: BTreeMap<usize, u64>,
+    pub writes: BTreeMap<usize, u64>,
+    pub max_memory: usize,
+}
+
+impl Profile {
+    pub fn record_execution(&mut self, addr: usize, mnemonic: &'static str, memory: usize) {
+        self.steps += 1;
+        *self.executions.entry(addr).or_default() += 1;
+        *self.opcodes.entry(mnemonic).or_default() += 1;
+        self.max_memory = self.max_memory.max(memory);
+    }
+
+    pub fn record_read(&mut self, addr: usize) {
+        *self.reads.entry(addr).or_default() += 1;
+    }
+
+    pub fn record_write(&mut self, addr: usize) {
+        *self.writes.entry(addr).or_default() += 1;
+    }
+
+    pub fn executions_at(&self, addr: usize) -> u64 {
+        self.executions.get(&addr).copied().unwrap_or(0)
+    }
+
+    /// Instructions in `disassembly` that never ran.
+    pub fn unexecuted<'a>(
+        &'a self,
+        disassembly: &'a Disassembly,
+    ) -> impl Iterator<Item = usize> + 'a {
+        disassembly
+            .entries()
+            .iter()
+            .filter_map(move |entry| match entry {
+                Entry::Code { addr, .. } if self.executions_at(*addr) == 0 => Some(*addr),
+                _ => None,
+            })
+    }
+
+    pub fn to_json(&self) -> String {
+        fn object<K: fmt::Display>(map: &BTreeMap<K, u64>) -> String {
+            let fields: Vec<String> = map
+                .iter()
+                .map(|(key, count)| format!("\"{}\":{}", key, count))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+
+        format!(
+            "{{\"steps\":{},\"max_memory\":{},\"opcodes\":{},\"executions\":{},\"reads\":{},\"writes\":{}}}",
+            self.steps,
+            self.max_memory,
+            object(&self.opcodes),
+            object(&self.executions),
+            object(&self.reads),
+            object(&self.writes),
+        )
+    }
+
+    /// Renders `disassembly` with how often each instruction ran in the
+    /// left margin, and how often each data line was read and written in
+    /// its right margin. Code that never ran is marked with `-`.
+    pub fn annotate(&self, disassembly: &Disassembly) -> String {
+        let mut s = String::new();
+        let mut code = 0;
+        let mut covered = 0;
+
+        for entry in disassembly.entries() {
+            if let Some(label) = disassembly.label(entry.addr()) {
+                writeln!(s, "{:10}  {}:", "", label).unwrap();
+            }
+
+            let line = disassembly.format_entry(entry);
+            match entry {
+                Entry::Code { addr, .. } => {
+                    code += 1;
+                    let count = self.executions_at(*addr);
+                    if count > 0 {
+                        covered += 1;
+                        writeln!(s, "{:>10}        {}", count, line).unwrap();
+                    } else {
+                        writeln!(s, "{:>10}        {}", "-", line).unwrap();
+                    }
+                }
+                Entry::Data { addr, values } => {
+                    let range = *addr..addr + values.len();
+                    let reads: u64 = range.clone().filter_map(|a| self.reads.get(&a)).sum();
+                    let writes: u64 = range.filter_map(|a| self.writes.get(&a)).sum();
+                    if reads + writes > 0 {
+                        writeln!(s, "{:10}        {} ; r:{} w:{}", "", line, reads, writes)
+                            .unwrap();
+                    } else {
+                        writeln!(s, "{:10}        {}", "", line).unwrap();
+                    }
+                }
+            }
+        }
+
+        writeln!(s, "coverage: {} of {} instructions executed", covered, code).unwrap();
+        s
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "instructions executed: {}", self.steps)?;
+        writeln!(f, "max memory: {} cells", self.max_memory)?;
+
+        writeln!(f, "\nby opcode:")?;
+        let mut opcodes: Vec<(&&str, &u64)> = self.opcodes.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1));
+        for (mnemonic, count) in opcodes {
+            writeln!(f, "  {:4} {:>12}", mnemonic, count)?;
+        }
+
+        writeln!(f, "\nhottest instructions:")?;
+        let mut hottest: Vec<(&usize, &u64)> = self.executions.iter().collect();
+        hottest.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (addr, count) in hottest.into_iter().take(HOTTEST) {
+            writeln!(f, "  {:04} {:>12}", addr, count)?;
+        }
+
+        writeln!(f, "\nmost written memory:")?;
+        let mut written: Vec<(&usize, &u64)> = self.writes.iter().collect();
+        written.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (addr, count) in written.into_iter().take(HOTTEST) {
+            writeln!(f, "  {:04} {:>12}", addr, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn counts_executions_and_memory_accesses() {
+    use crate::intcode_computer::Machine;
+
+    let mut machine = Machine::new();
+    // Counts [9] down from 3 to 0
+    machine.init("1001,9,-1,9,1005,9,0,99,0,3").unwrap();
+    machine.enable_profiling();
+    machine.run_with_input(vec![]).unwrap();
+
+    let profile = machine.take_profile().unwrap();
+    assert_eq!(profile.steps, 7);
+    assert_eq!(profile.executions_at(0), 3);
+    assert_eq!(profile.executions_at(4), 3);
+    assert_eq!(profile.executions_at(7), 1);
+    assert_eq!(profile.opcodes.get("add"), Some(&3));
+    assert_eq!(profile.reads.get(&9), Some(&6));
+    assert_eq!(profile.writes.get(&9), Some(&3));
+    assert_eq!(profile.max_memory, 10);
+    assert!(machine.profile().is_none());
+
+    assert_eq!(
+        profile.to_json(),
+        "{\"steps\":7,\"max_memory\":10,\"opcodes\":{\"add\":3,\"hlt\":1,\"jt\":3},\
+         \"executions\":{\"0\":3,\"4\":3,\"7\":1},\"reads\":{\"9\":6},\"writes\":{\"9\":3}}"
+    );
+}
+
+#[test]
+fn annotates_disassembly_with_coverage() {
+    use crate::intcode_computer::Machine;
+    use crate::intcode_disassembler::disassemble;
+
+    // Skips the output when its input is 0
+    let program = "3,11,1006,11,7,4,11,99,4,11,99,0";
+    let mut machine = Machine::new();
+    machine.init(program).unwrap();
+    machine.enable_profiling();
+    machine.run_with_input(vec![0]).unwrap();
+
+    let disassembly = disassemble(machine.mem());
+    let profile = machine.profile().unwrap();
+    assert_eq!(
+        profile.unexecuted(&disassembly).collect::<Vec<usize>>(),
+        vec![5]
+    );
+
+    let annotated = profile.annotate(&disassembly);
+    assert!(annotated.contains("         -        0005: out [var_11]"));
+    assert!(annotated.contains("0011: data 0 ; r:1 w:1"));
+    assert!(annotated.ends_with("coverage: 3 of 4 instructions executed\n"));
+}
@@ -0,0 +1,364 @@
+use crate::intcode_computer::{Error, Int, Machine, OperationState, Status};
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Something a traced machine did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// A value consumed by an input instruction.
+    Input(Int),
+    /// A value produced by an output instruction.
+    Output(Int),
+    /// An instruction executed at `addr`, with where execution continued,
+    /// the relative base afterwards and the memory cell it wrote, if any.
+    Step {
+        addr: usize,
+        next: usize,
+        relative_base: isize,
+        write: Option<(usize, Int)>,
+    },
+    /// The run was cut short after `steps` instructions, by an error or a
+    /// limit, rather than halting.
+    Stop { steps: u64 },
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceEvent::Input(val) => write!(f, "in {}", val),
+            TraceEvent::Output(val) => write!(f, "out {}", val),
+            TraceEvent::Step {
+                addr,
+                next,
+                relative_base,
+                write,
+            } => {
+                write!(f, "step {} next {} rb {}", addr, next, relative_base)?;
+                if let Some((addr, val)) = write {
+                    write!(f, " write {} {}", addr, val)?;
+                }
+                Ok(())
+            }
+            TraceEvent::Stop { steps } => write!(f, "stop {}", steps),
+        }
+    }
+}
+
+impl FromStr for TraceEvent {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |val: &str| -> Result<Int, Error> {
+            val.parse()
+                .map_err(|_| Error::TraceParseError(format!("invalid number `{}` in `{}`", val, s)))
+        };
+        let step = |addr: &str, next: &str, rb: &str, write| -> Result<TraceEvent, Error> {
+            Ok(TraceEvent::Step {
+                addr: number(addr)? as usize,
+                next: number(next)? as usize,
+                relative_base: number(rb)? as isize,
+                write,
+            })
+        };
+
+        let words: Vec<&str> = s.split_whitespace().collect();
+        match words[..] {
+            ["in", val] => Ok(TraceEvent::Input(number(val)?)),
+            ["out", val] => Ok(TraceEvent::Output(number(val)?)),
+            ["stop", steps] => Ok(TraceEvent::Stop {
+                steps: number(steps)? as u64,
+            }),
+            ["step", addr, "next", next, "rb", rb] => step(addr, next, rb, None),
+            ["step", addr, "next", next, "rb", rb, "write", dest, val] => {
+                step(addr, next, rb, Some((number(dest)? as usize, number(val)?)))
+            }
+            _ => Err(Error::TraceParseError(format!("invalid event `{}`", s))),
+        }
+    }
+}
+
+/// Everything a machine consumed and produced while tracing was enabled,
+/// in order, and optionally every instruction it executed.
+///
+/// Traces are saved as one event per line, and can be loaded back with
+/// `Trace::from_str`. Two traces are equal if they recorded the same
+/// events.
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    instructions: bool,
+    events: Vec<TraceEvent>,
+    steps: u64,
+    pending_write: Option<(usize, Int)>,
+}
+
+impl PartialEq for Trace {
+    fn eq(&self, other: &Self) -> bool {
+        self.instructions == other.instructions && self.events == other.events
+    }
+}
+
+impl Eq for Trace {}
+
+impl Trace {
+    /// Creates an empty trace, which records every executed instruction
+    /// as well as I/O if `instructions` is set.
+    pub fn new(instructions: bool) -> Self {
+        Self {
+            instructions,
+            ..Self::default()
+        }
+    }
+
+    pub fn records_instructions(&self) -> bool {
+        self.instructions
+    }
+
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// The number of instructions a run that was cut short had executed,
+    /// if it was.
+    pub fn stopped_after(&self) -> Option<u64> {
+        match self.events.last() {
+            Some(TraceEvent::Stop { steps }) => Some(*steps),
+            _ => None,
+        }
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = Int> + '_ {
+        self.events.iter().filter_map(|event| match event {
+            TraceEvent::Input(val) => Some(*val),
+            _ => None,
+        })
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = Int> + '_ {
+        self.events.iter().filter_map(|event| match event {
+            TraceEvent::Output(val) => Some(*val),
+            _ => None,
+        })
+    }
+
+    pub fn record_input(&mut self, val: Int) {
+        self.events.push(TraceEvent::Input(val));
+    }
+
+    pub fn record_output(&mut self, val: Int) {
+        self.events.push(TraceEvent::Output(val));
+    }
+
+    pub fn record_write(&mut self, addr: usize, val: Int) {
+        if self.instructions {
+            self.pending_write = Some((addr, val));
+        }
+    }
+
+    pub fn record_step(&mut self, addr: usize, next: usize, relative_base: isize) {
+        let write = self.pending_write.take();
+        self.steps += 1;
+        if self.instructions {
+            self.events.push(TraceEvent::Step {
+                addr,
+                next,
+                relative_base,
+                write,
+            });
+        }
+    }
+
+    /// Notes that the run stopped before halting, so a replay knows to stop
+    /// at the same point.
+    pub fn record_stop(&mut self) {
+        self.events.push(TraceEvent::Stop { steps: self.steps });
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in self.events.iter() {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Trace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let events = s
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(TraceEvent::from_str)
+            .collect::<Result<Vec<TraceEvent>, Error>>()?;
+        let instructions = events
+            .iter()
+            .any(|event| matches!(event, TraceEvent::Step { .. }));
+
+        let mut trace = Self {
+            instructions,
+            events,
+            steps: 0,
+            pending_write: None,
+        };
+        trace.steps = match trace.stopped_after() {
+            Some(steps) => steps,
+            None => trace
+                .events
+                .iter()
+                .filter(|event| matches!(event, TraceEvent::Step { .. }))
+                .count() as u64,
+        };
+        Ok(trace)
+    }
+}
+
+/// Runs a copy of `machine` with the inputs recorded in `trace`, checking
+/// that it does exactly what was recorded. Instructions are only compared
+/// if the trace recorded them. A trace of a run that was cut short is
+/// replayed up to the same point. Returns the new trace.
+pub fn replay(machine: &Machine, trace: &Trace) -> Result<Trace, Error> {
+    let mut machine = machine.clone();
+    machine.enable_tracing(trace.records_instructions());
+    let result = match trace.stopped_after() {
+        Some(steps) => run_steps(&mut machine, trace.inputs(), steps),
+        None => machine.run_with_input(trace.inputs()).map(|_| ()),
+    };
+    let mut replayed = machine.take_trace().unwrap();
+    if result.is_ok() && trace.stopped_after().is_some() {
+        replayed.record_stop();
+    }
+
+    let mismatch = trace
+        .events()
+        .iter()
+        .zip(replayed.events())
+        .position(|(expected, found)| expected != found);
+    if let Some(i) = mismatch {
+        return Err(Error::ReplayError(format!(
+            "event {}: expected `{}`, found `{}`",
+            i,
+            trace.events()[i],
+            replayed.events()[i]
+        )));
+    }
+    result?;
+
+    let (expected, found) = (trace.events().len(), replayed.events().len());
+    if expected != found {
+        return Err(Error::ReplayError(format!(
+            "expected {} events, found {}",
+            expected, found
+        )));
+    }
+
+    Ok(replayed)
+}
+
+/// Executes up to `steps` instructions, or until the program halts.
+fn run_steps<I>(machine: &mut Machine, input: I, steps: u64) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Int>,
+{
+    for val in input {
+        machine.push_input(val);
+    }
+    for _ in 0..steps {
+        if *machine.state() == OperationState::Exited {
+            break;
+        }
+        if let Status::AwaitingInput = machine.step()? {
+            return Err(Error::InvalidInputError(
+                "program needs more input".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn records_io_and_instructions() {
+    let mut machine = Machine::new();
+    machine.init("3,9,1001,9,5,9,4,9,99,0").unwrap();
+    machine.enable_tracing(true);
+    assert_eq!(machine.run_with_input(vec![10]).unwrap(), vec![15]);
+
+    let trace = machine.take_trace().unwrap();
+    assert_eq!(trace.inputs().collect::<Vec<Int>>(), vec![10]);
+    assert_eq!(trace.outputs().collect::<Vec<Int>>(), vec![15]);
+    assert_eq!(
+        trace.to_string(),
+        "in 10
+step 0 next 2 rb 0 write 9 10
+step 2 next 6 rb 0 write 9 15
+out 15
+step 6 next 8 rb 0
+step 8 next 9 rb 0
+"
+    );
+    assert_eq!(Trace::from_str(&trace.to_string()).unwrap(), trace);
+}
+
+#[test]
+fn replays_recorded_inputs() {
+    let mut machine = Machine::new();
+    // Outputs double each input until it reads a 0
+    machine
+        .init("3,15,1006,15,14,1002,15,2,15,4,15,1105,1,0,99,0")
+        .unwrap();
+
+    let mut recording = machine.clone();
+    recording.enable_tracing(false);
+    recording.run_with_input(vec![1, 2, 3, 0]).unwrap();
+    let trace = recording.take_trace().unwrap();
+    assert_eq!(replay(&machine, &trace).unwrap(), trace);
+
+    let mut changed = machine.clone();
+    changed.write(7, 3);
+    match replay(&changed, &trace) {
+        Err(Error::ReplayError(message)) => {
+            assert_eq!(message, "event 1: expected `out 2`, found `out 3`")
+        }
+        other => panic!("unexpected replay result {:?}", other),
+    }
+
+    let truncated = Trace::from_str("in 1\nout 2\n").unwrap();
+    assert!(replay(&machine, &truncated).is_err());
+}
+
+#[test]
+fn replays_runs_that_were_cut_short() {
+    use crate::intcode_computer::Limits;
+
+    let mut machine = Machine::new();
+    // Outputs double each input until it reads a 0
+    machine
+        .init("3,15,1006,15,14,1002,15,2,15,4,15,1105,1,0,99,0")
+        .unwrap();
+
+    for instructions in [false, true] {
+        let mut recording = machine.clone();
+        recording.enable_tracing(instructions);
+        recording.set_limits(Limits {
+            max_steps: Some(7),
+            ..Limits::default()
+        });
+        assert!(matches!(
+            recording.run_with_input(vec![1, 2, 3, 0]),
+            Err(Error::LimitExceeded { .. })
+        ));
+        let mut trace = recording.take_trace().unwrap();
+        trace.record_stop();
+        assert_eq!(trace.stopped_after(), Some(7));
+        assert_eq!(trace.outputs().collect::<Vec<Int>>(), vec![2]);
+
+        let trace = Trace::from_str(&trace.to_string()).unwrap();
+        assert_eq!(replay(&machine, &trace).unwrap(), trace);
+    }
+
+    // Stopping anywhere else is a mismatch
+    let trace = Trace::from_str("in 1\nout 2\nin 2\nstop 9\n").unwrap();
+    assert!(replay(&machine, &trace).is_err());
+}
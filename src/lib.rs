@@ -1,6 +1,21 @@
 use std::io::{self, Read};
 pub mod grid;
+pub mod intcode_assembler;
 pub mod intcode_computer;
+pub mod intcode_debugger;
+pub mod intcode_disassembler;
+pub mod intcode_engine;
+pub mod intcode_history;
+pub mod intcode_io;
+pub mod intcode_network;
+pub mod intcode_opcodes;
+pub mod intcode_pipeline;
+pub mod intcode_profiler;
+pub mod intcode_trace;
+pub mod ocr;
+pub mod raster;
+pub mod repair_droid;
+pub mod space_image;
 
 pub fn get_input() -> io::Result<String> {
     let mut s = String::new();
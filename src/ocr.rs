@@ -0,0 +1,253 @@
+use crate::grid::{Coord, Grid};
+
+/// Glyphs of the 4x6 font, drawn with `#` for lit pixels. Letters are laid
+/// out five columns apart, so a letter five pixels wide like Y touches the
+/// one after it. Each glyph is trimmed of blank columns before matching.
+const SMALL_FONT: &[(char, &str)] = &[
+    ('A', ".##.|#..#|#..#|####|#..#|#..#"),
+    ('B', "###.|#..#|###.|#..#|#..#|###."),
+    ('C', ".##.|#..#|#...|#...|#..#|.##."),
+    ('E', "####|#...|###.|#...|#...|####"),
+    ('F', "####|#...|###.|#...|#...|#..."),
+    ('G', ".##.|#..#|#...|#.##|#..#|.###"),
+    ('H', "#..#|#..#|####|#..#|#..#|#..#"),
+    ('I', ".###|..#.|..#.|..#.|..#.|.###"),
+    ('J', "..##|...#|...#|...#|#..#|.##."),
+    ('K', "#..#|#.#.|##..|#.#.|#.#.|#..#"),
+    ('L', "#...|#...|#...|#...|#...|####"),
+    ('O', ".##.|#..#|#..#|#..#|#..#|.##."),
+    ('P', "###.|#..#|#..#|###.|#...|#..."),
+    ('R', "###.|#..#|#..#|###.|#.#.|#..#"),
+    ('S', ".###|#...|#...|.##.|...#|###."),
+    ('U', "#..#|#..#|#..#|#..#|#..#|.##."),
+    ('Y', "#...#|#...#|.#.#.|..#..|..#..|..#.."),
+    ('Z', "####|...#|..#.|.#..|#...|####"),
+];
+
+/// Glyphs of the 6x10 font.
+const LARGE_FONT: &[(char, &str)] = &[
+    (
+        'A',
+        "..##..|.#..#.|#....#|#....#|#....#|######|#....#|#....#|#....#|#....#",
+    ),
+    (
+        'B',
+        "#####.|#....#|#....#|#....#|#####.|#....#|#....#|#....#|#....#|#####.",
+    ),
+    (
+        'C',
+        ".####.|#....#|#.....|#.....|#.....|#.....|#.....|#.....|#....#|.####.",
+    ),
+    (
+        'E',
+        "######|#.....|#.....|#.....|#####.|#.....|#.....|#.....|#.....|######",
+    ),
+    (
+        'F',
+        "######|#.....|#.....|#.....|#####.|#.....|#.....|#.....|#.....|#.....",
+    ),
+    (
+        'G',
+        ".####.|#....#|#.....|#.....|#.....|#..###|#....#|#....#|#...##|.###.#",
+    ),
+    (
+        'H',
+        "#....#|#....#|#....#|#....#|######|#....#|#....#|#....#|#....#|#....#",
+    ),
+    (
+        'J',
+        "...###|....#.|....#.|....#.|....#.|....#.|....#.|#...#.|#...#.|.###..",
+    ),
+    (
+        'K',
+        "#....#|#...#.|#..#..|#.#...|##....|##....|#.#...|#..#..|#...#.|#....#",
+    ),
+    (
+        'L',
+        "#.....|#.....|#.....|#.....|#.....|#.....|#.....|#.....|#.....|######",
+    ),
+    (
+        'N',
+        "#....#|##...#|##...#|#.#..#|#.#..#|#..#.#|#..#.#|#...##|#...##|#....#",
+    ),
+    (
+        'P',
+        "#####.|#....#|#....#|#....#|#####.|#.....|#.....|#.....|#.....|#.....",
+    ),
+    (
+        'R',
+        "#####.|#....#|#....#|#....#|#####.|#..#..|#...#.|#...#.|#....#|#....#",
+    ),
+    (
+        'X',
+        "#....#|#....#|.#..#.|.#..#.|..##..|..##..|.#..#.|.#..#.|#....#|#....#",
+    ),
+    (
+        'Z',
+        "######|.....#|.....#|....#.|...#..|..#...|.#....|#.....|#.....|######",
+    ),
+];
+
+type Pixels = Vec<Vec<bool>>;
+
+/// Reads the block letters drawn in `grid`, where `lit` says which cells
+/// are part of a letter. Unlit and missing cells are both background.
+///
+/// Returns `None` if the letters aren't in a known font or any of them
+/// can't be recognised.
+pub fn recognize<T, F>(grid: &Grid<T>, lit: F) -> Option<String>
+where
+    F: Fn(&T) -> bool,
+{
+    let mut lit_cells = Grid::new();
+    for (coord, cell) in grid.iter() {
+        if lit(cell) {
+            lit_cells.insert(coord.clone(), ());
+        }
+    }
+    if lit_cells.is_empty() {
+        return None;
+    }
+
+    let ((min_x, min_y), (max_x, max_y)) = lit_cells.bounds();
+    let rows: Pixels = (min_y..=max_y)
+        .map(|y| {
+            (min_x..=max_x)
+                .map(|x| lit_cells.contains_key(&Coord(x, y)))
+                .collect()
+        })
+        .collect();
+    recognize_pixels(&rows)
+}
+
+/// Like `recognize`, for letters already laid out in rows of pixels.
+pub fn recognize_pixels(rows: &[Vec<bool>]) -> Option<String> {
+    let rows: Vec<&Vec<bool>> = rows.iter().filter(|row| row.contains(&true)).collect();
+    let font = match rows.len() {
+        6 => SMALL_FONT,
+        10 => LARGE_FONT,
+        _ => return None,
+    };
+    let width = rows.iter().map(|row| row.len()).max()?;
+    let lit = |x: usize, y: usize| rows[y].get(x).copied().unwrap_or(false);
+
+    // Letters can touch, such as a Y with another letter straight after it,
+    // so rather than splitting on blank columns each glyph is matched where
+    // the next one starts. Wider glyphs go first so a narrow one can't match
+    // just the start of a wider letter.
+    let mut glyphs: Vec<(char, Pixels)> = font
+        .iter()
+        .map(|(letter, drawn)| (*letter, trim(parse(drawn))))
+        .collect();
+    glyphs.sort_by_key(|(_, glyph)| std::cmp::Reverse(glyph[0].len()));
+
+    let mut text = String::new();
+    let mut x = 0;
+    while x < width {
+        if !(0..rows.len()).any(|y| lit(x, y)) {
+            x += 1;
+            continue;
+        }
+
+        let (letter, glyph) = glyphs.iter().find(|(_, glyph)| {
+            glyph
+                .iter()
+                .enumerate()
+                .all(|(y, row)| row.iter().enumerate().all(|(i, p)| lit(x + i, y) == *p))
+        })?;
+        text.push(*letter);
+        x += glyph[0].len();
+    }
+
+    Some(text)
+}
+
+fn parse(glyph: &str) -> Pixels {
+    glyph
+        .split('|')
+        .map(|row| row.chars().map(|c| c == '#').collect())
+        .collect()
+}
+
+/// Drops blank columns from either side of a glyph.
+fn trim(glyph: Pixels) -> Pixels {
+    let lit = |x: usize| glyph.iter().any(|row| row[x]);
+    let width = glyph[0].len();
+    let start = (0..width).find(|x| lit(*x)).unwrap_or(0);
+    let end = (0..width).rev().find(|x| lit(*x)).map_or(0, |x| x + 1);
+    glyph.iter().map(|row| row[start..end].to_vec()).collect()
+}
+
+#[allow(dead_code)]
+fn to_grid(drawing: &str) -> Grid<char> {
+    let mut grid = Grid::new();
+    for (y, line) in drawing.lines().enumerate() {
+        for (x, c) in line.trim().chars().enumerate() {
+            grid.insert(Coord(x as i64, y as i64), c);
+        }
+    }
+    grid
+}
+
+#[test]
+fn reads_the_small_font() {
+    let grid = to_grid(
+        ".##..###..#..#.####..###..##..#...#
+         #..#.#..#.#..#.#......#..#..#.#...#
+         #..#.###..####.###....#..#.....#.#.
+         ####.#..#.#..#.#......#..#......#..
+         #..#.#..#.#..#.#......#..#..#...#..
+         #..#.###..#..#.####..###..##....#..",
+    );
+    assert_eq!(recognize(&grid, |c| *c == '#'), Some("ABHEICY".to_string()));
+
+    // Unlit cells around the letters don't matter
+    let mut grid = grid;
+    grid.insert(Coord(-3, -2), '.');
+    assert_eq!(recognize(&grid, |c| *c == '#'), Some("ABHEICY".to_string()));
+
+    grid.insert(Coord(40, 2), '#');
+    assert_eq!(recognize(&grid, |c| *c == '#'), None);
+}
+
+#[test]
+fn reads_letters_that_touch() {
+    let grid = to_grid(
+        "#..#.#...#.##..###..
+         #..#.#...##..#.#..#.
+         #..#..#.#.#..#.###..
+         #..#...#..####.#..#.
+         #..#...#..#..#.#..#.
+         .##....#..#..#.###..",
+    );
+    assert_eq!(recognize(&grid, |c| *c == '#'), Some("UYAB".to_string()));
+}
+
+#[test]
+fn reads_the_large_font() {
+    let grid = to_grid(
+        "#....#..######..#####.
+         #....#.......#..#....#
+         .#..#........#..#....#
+         .#..#.......#...#....#
+         ..##.......#....#####.
+         ..##......#.....#.....
+         .#..#....#......#.....
+         .#..#...#.......#.....
+         #....#..#.......#.....
+         #....#..######..#.....",
+    );
+    assert_eq!(recognize(&grid, |c| *c == '#'), Some("XZP".to_string()));
+}
+
+#[test]
+fn knows_every_glyph() {
+    for (font, height) in [(SMALL_FONT, 6), (LARGE_FONT, 10)] {
+        for (letter, drawn) in font {
+            let rows = parse(drawn);
+            assert_eq!(rows.len(), height, "{} is the wrong height", letter);
+            assert!(rows.iter().all(|row| row.len() == rows[0].len()));
+            assert_eq!(recognize_pixels(&rows), Some(letter.to_string()));
+        }
+    }
+}
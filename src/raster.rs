@@ -0,0 +1,291 @@
+use crate::grid::{Coord, Grid};
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub type Rgb = [u8; 3];
+
+pub const BLACK: Rgb = [0, 0, 0];
+pub const WHITE: Rgb = [255, 255, 255];
+
+/// Largest amount of data a stored deflate block can hold.
+const MAX_STORED_BLOCK: usize = 65535;
+
+/// A rectangular image of RGB pixels, for saving puzzle output as a
+/// picture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Raster {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Raster {
+    pub fn new(width: usize, height: usize, fill: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![fill; width * height],
+        }
+    }
+
+    /// Draws the bounding box of `grid`, with each cell `scale` pixels
+    /// square and coloured by `color`, which is given `None` for cells
+    /// missing from the grid.
+    pub fn from_grid<T, F>(grid: &Grid<T>, scale: usize, color: F) -> Self
+    where
+        F: Fn(Option<&T>) -> Rgb,
+    {
+        if grid.is_empty() {
+            return Self::new(0, 0, BLACK);
+        }
+
+        let ((min_x, min_y), (max_x, max_y)) = grid.bounds();
+        let width = (max_x - min_x + 1) as usize;
+        let height = (max_y - min_y + 1) as usize;
+
+        let mut raster = Self::new(width, height, BLACK);
+        for y in 0..height {
+            for x in 0..width {
+                let cell = grid.get(&Coord(min_x + x as i64, min_y + y as i64));
+                raster.set(x, y, color(cell));
+            }
+        }
+        raster.scaled(scale)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// Blows every pixel up into a `scale` by `scale` square. Panics if
+    /// `scale` is 0.
+    pub fn scaled(&self, scale: usize) -> Self {
+        assert!(scale >= 1, "can't scale an image by 0");
+        let mut raster = Self::new(self.width * scale, self.height * scale, BLACK);
+        for y in 0..raster.height {
+            for x in 0..raster.width {
+                raster.set(x, y, self.get(x / scale, y / scale));
+            }
+        }
+        raster
+    }
+
+    /// Encodes as a plain PBM bitmap, with dark pixels set.
+    pub fn to_pbm(&self) -> Vec<u8> {
+        let mut out = format!("P1\n{} {}\n", self.width, self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            let bits: Vec<&str> = row
+                .iter()
+                .map(|pixel| if luminance(*pixel) < 128 { "1" } else { "0" })
+                .collect();
+            out.push_str(&bits.join(" "));
+            out.push('\n');
+        }
+        out.into_bytes()
+    }
+
+    /// Encodes as a plain PGM greymap.
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut out = format!("P2\n{} {}\n255\n", self.width, self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            let greys: Vec<String> = row
+                .iter()
+                .map(|pixel| luminance(*pixel).to_string())
+                .collect();
+            out.push_str(&greys.join(" "));
+            out.push('\n');
+        }
+        out.into_bytes()
+    }
+
+    /// Encodes as a plain PPM pixmap.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut out = format!("P3\n{} {}\n255\n", self.width, self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            let channels: Vec<String> = row
+                .iter()
+                .flatten()
+                .map(|channel| channel.to_string())
+                .collect();
+            out.push_str(&channels.join(" "));
+            out.push('\n');
+        }
+        out.into_bytes()
+    }
+
+    /// Encodes as a truecolour PNG. The image data is stored without
+    /// compression, so any PNG reader can open it without this needing a
+    /// deflate implementation.
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.pixels.chunks(self.width.max(1)).take(self.height) {
+            // No filter
+            raw.push(0);
+            for pixel in row {
+                raw.extend_from_slice(pixel);
+            }
+        }
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, deflate, no filtering, no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut out = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+        png_chunk(&mut out, b"IHDR", &header);
+        png_chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+        png_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    /// Saves in the format named by `path`'s extension: `pbm`, `pgm`, `ppm`
+    /// or `png`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let data = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("pbm") => self.to_pbm(),
+            Some("pgm") => self.to_pgm(),
+            Some("ppm") => self.to_ppm(),
+            Some("png") => self.to_png(),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("don't know what image format {} is", path.display()),
+                ))
+            }
+        };
+        fs::write(path, data)
+    }
+}
+
+/// Parses a colour written as `rrggbb` hex, with or without a leading `#`.
+pub fn parse_color(s: &str) -> Option<Rgb> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn luminance([r, g, b]: Rgb) -> u32 {
+    (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        out.push(last as u8);
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[test]
+fn checksums_match_known_values() {
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+}
+
+#[test]
+fn encodes_grids() {
+    let mut grid = Grid::new();
+    grid.insert(Coord(-1, 0), true);
+    grid.insert(Coord(1, 1), false);
+    let color = |cell: Option<&bool>| match cell {
+        Some(true) => WHITE,
+        _ => BLACK,
+    };
+
+    let raster = Raster::from_grid(&grid, 1, color);
+    assert_eq!((raster.width(), raster.height()), (3, 2));
+    assert_eq!(
+        String::from_utf8(raster.to_pbm()).unwrap(),
+        "P1\n3 2\n0 1 1\n1 1 1\n"
+    );
+
+    let scaled = Raster::from_grid(&grid, 2, color);
+    assert_eq!((scaled.width(), scaled.height()), (6, 4));
+    assert_eq!(scaled.get(1, 1), WHITE);
+    assert_eq!(scaled.get(2, 1), BLACK);
+
+    assert_eq!(
+        String::from_utf8(raster.to_pgm()).unwrap(),
+        "P2\n3 2\n255\n255 0 0\n0 0 0\n"
+    );
+    assert_eq!(
+        String::from_utf8(raster.to_ppm()).unwrap(),
+        "P3\n3 2\n255\n255 255 255 0 0 0 0 0 0\n0 0 0 0 0 0 0 0 0\n"
+    );
+
+    let png = raster.to_png();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..24], &[0, 0, 0, 3, 0, 0, 0, 2]);
+    // Two rows of a filter byte and three pixels, in a single stored block
+    assert_eq!(&png[33..37], &[0, 0, 0, 2 + 1 + 2 + 2 + 2 * 10 + 4]);
+    assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+
+    assert_eq!(parse_color("#ff8000"), Some([255, 128, 0]));
+    assert_eq!(parse_color("12345"), None);
+}
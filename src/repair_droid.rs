@@ -0,0 +1,335 @@
+use crate::grid::{Coord, Grid};
+use crate::intcode_computer::{Error, Int, IntcodeComputer, Machine, Status};
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    North,
+    South,
+    West,
+    East,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::South,
+        Direction::West,
+        Direction::East,
+    ];
+
+    /// The movement command that sends the droid this way.
+    pub fn command(self) -> Int {
+        match self {
+            Direction::North => 1,
+            Direction::South => 2,
+            Direction::West => 3,
+            Direction::East => 4,
+        }
+    }
+
+    pub fn reverse(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::East => Direction::West,
+        }
+    }
+
+    /// The position next to `from` in this direction. North is up, so y
+    /// grows southwards like it does on screen.
+    pub fn from(self, from: &Coord) -> Coord {
+        match self {
+            Direction::North => Coord(from.0, from.1 - 1),
+            Direction::South => Coord(from.0, from.1 + 1),
+            Direction::West => Coord(from.0 - 1, from.1),
+            Direction::East => Coord(from.0 + 1, from.1),
+        }
+    }
+}
+
+/// What the droid reports after being told to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reply {
+    /// It hit a wall and stayed where it was.
+    Wall,
+    Moved,
+    /// It moved and is now at the oxygen system.
+    FoundOxygen,
+}
+
+impl TryFrom<Int> for Reply {
+    type Error = Error;
+
+    fn try_from(val: Int) -> Result<Self, Self::Error> {
+        match val {
+            0 => Ok(Reply::Wall),
+            1 => Ok(Reply::Moved),
+            2 => Ok(Reply::FoundOxygen),
+            _ => Err(Error::InvalidOutputError(format!(
+                "{} is not a droid status",
+                val
+            ))),
+        }
+    }
+}
+
+/// Something that can be moved around the area one step at a time.
+pub trait Droid {
+    fn try_move(&mut self, dir: Direction) -> Result<Reply, Error>;
+}
+
+impl Droid for Machine {
+    fn try_move(&mut self, dir: Direction) -> Result<Reply, Error> {
+        self.push_input(dir.command());
+        match self.resume()? {
+            Status::Output(val) => Reply::try_from(val),
+            status => Err(Error::InvalidOutputError(format!(
+                "droid stopped with {:?} instead of replying",
+                status
+            ))),
+        }
+    }
+}
+
+/// Drives the computer's machine directly, leaving its channels unused.
+impl Droid for IntcodeComputer<'_> {
+    fn try_move(&mut self, dir: Direction) -> Result<Reply, Error> {
+        Droid::try_move(&mut **self, dir)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tile {
+    #[default]
+    Unknown,
+    Wall,
+    Open,
+    Oxygen,
+}
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = match self {
+            Tile::Unknown => ' ',
+            Tile::Wall => '#',
+            Tile::Open => '.',
+            Tile::Oxygen => 'O',
+        };
+        write!(f, "{}", c)
+    }
+}
+
+/// Everything the droid has found so far, relative to where it started at
+/// `Coord(0, 0)`.
+#[derive(Debug, Clone, Default)]
+pub struct Area {
+    pub map: Grid<Tile>,
+    pub droid: Coord,
+    pub oxygen: Option<Coord>,
+}
+
+impl Area {
+    /// Number of steps from `from` to each open position reachable from it.
+    pub fn distances_from(&self, from: &Coord) -> HashMap<Coord, usize> {
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        distances.insert(from.clone(), 0);
+        queue.push_back(from.clone());
+
+        while let Some(pos) = queue.pop_front() {
+            let distance = distances[&pos];
+            for dir in Direction::ALL {
+                let next = dir.from(&pos);
+                let open = matches!(self.map.get(&next), Some(Tile::Open | Tile::Oxygen));
+                if open && !distances.contains_key(&next) {
+                    distances.insert(next.clone(), distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+
+    /// Fewest steps from the starting position to the oxygen system.
+    pub fn oxygen_distance(&self) -> Option<usize> {
+        let oxygen = self.oxygen.as_ref()?;
+        self.distances_from(&Coord(0, 0)).get(oxygen).copied()
+    }
+
+    /// Minutes it takes oxygen to spread from the oxygen system to every
+    /// open position, a step a minute.
+    pub fn fill_time(&self) -> Option<usize> {
+        let oxygen = self.oxygen.as_ref()?;
+        self.distances_from(oxygen).into_values().max()
+    }
+}
+
+/// Maps out the whole area by walking the droid into every position next
+/// to one it's been to, backtracking once there's nowhere new to go, and
+/// ends up back at the start. `on_move` is called after every step.
+pub fn explore<D, F>(droid: &mut D, mut on_move: F) -> Result<Area, Error>
+where
+    D: Droid + ?Sized,
+    F: FnMut(&Area),
+{
+    let mut area = Area::default();
+    area.map.insert(Coord(0, 0), Tile::Open);
+    visit(droid, &mut area, &mut on_move)?;
+    Ok(area)
+}
+
+fn visit<D, F>(droid: &mut D, area: &mut Area, on_move: &mut F) -> Result<(), Error>
+where
+    D: Droid + ?Sized,
+    F: FnMut(&Area),
+{
+    for dir in Direction::ALL {
+        let next = dir.from(&area.droid);
+        if area.map.contains_key(&next) {
+            continue;
+        }
+
+        let tile = match droid.try_move(dir)? {
+            Reply::Wall => {
+                area.map.insert(next, Tile::Wall);
+                continue;
+            }
+            Reply::Moved => Tile::Open,
+            Reply::FoundOxygen => {
+                area.oxygen = Some(next.clone());
+                Tile::Oxygen
+            }
+        };
+        area.map.insert(next.clone(), tile);
+        let back = std::mem::replace(&mut area.droid, next);
+        on_move(area);
+
+        visit(droid, area, on_move)?;
+
+        if droid.try_move(dir.reverse())? == Reply::Wall {
+            return Err(Error::InvalidOutputError(
+                "droid couldn't retrace its steps".to_string(),
+            ));
+        }
+        area.droid = back;
+        on_move(area);
+    }
+
+    Ok(())
+}
+
+/// A droid walking around a map drawn with `#` for walls, `O` for the
+/// oxygen system and `D` for where it starts.
+#[allow(dead_code)]
+struct MockDroid {
+    map: Grid<char>,
+    pos: Coord,
+}
+
+#[allow(dead_code)]
+impl MockDroid {
+    fn new(map: &str) -> Self {
+        let mut grid = Grid::new();
+        let mut start = Coord(0, 0);
+        for (y, line) in map.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == 'D' {
+                    start = Coord(x as i64, y as i64);
+                }
+                grid.insert(Coord(x as i64, y as i64), c);
+            }
+        }
+
+        // Put the start at the origin, like the droid sees it
+        let mut map = Grid::new();
+        for (Coord(x, y), c) in grid.drain() {
+            map.insert(Coord(x - start.0, y - start.1), c);
+        }
+        Self {
+            map,
+            pos: Coord(0, 0),
+        }
+    }
+}
+
+impl Droid for MockDroid {
+    fn try_move(&mut self, dir: Direction) -> Result<Reply, Error> {
+        let next = dir.from(&self.pos);
+        match self.map.get(&next).copied().unwrap_or('#') {
+            '#' | ' ' => Ok(Reply::Wall),
+            c => {
+                self.pos = next;
+                if c == 'O' {
+                    Ok(Reply::FoundOxygen)
+                } else {
+                    Ok(Reply::Moved)
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn maps_the_area_and_finds_oxygen() {
+    let mut droid = MockDroid::new(
+        "\
+#########
+#D..#...#
+##.##.#.#
+#...#.#O#
+#.#...###
+#########",
+    );
+    let mut steps = 0;
+    let area = explore(&mut droid, |_| steps += 1).unwrap();
+
+    assert_eq!(droid.pos, Coord(0, 0));
+    assert_eq!(area.droid, Coord(0, 0));
+    assert_eq!(area.oxygen, Some(Coord(6, 2)));
+    assert_eq!(area.oxygen_distance(), Some(14));
+    assert_eq!(area.map.get(&Coord(3, 0)), Some(&Tile::Wall));
+    assert_eq!(area.map.get(&Coord(6, 1)), Some(&Tile::Open));
+    // Every open position is walked into and back out of again
+    assert_eq!(area.distances_from(&Coord(0, 0)).len(), 18);
+    assert_eq!(steps, 2 * 17);
+}
+
+#[test]
+fn fills_the_area_with_oxygen() {
+    // The example from the puzzle
+    let mut droid = MockDroid::new(
+        "\
+ ##
+#D.##
+#.#..#
+#.O.#
+ ###",
+    );
+    let area = explore(&mut droid, |_| {}).unwrap();
+
+    assert_eq!(area.oxygen_distance(), Some(3));
+    assert_eq!(area.fill_time(), Some(4));
+    assert_eq!(
+        area.map.to_string(),
+        " ##   \n#..## \n#.#..#\n#.O.# \n ###  \n"
+    );
+}
+
+#[test]
+fn drives_intcode_droids() {
+    // Replies to any command with a wall, except that heading north finds
+    // the oxygen system
+    let mut machine = Machine::new();
+    machine
+        .init("3,20,1008,20,1,21,1002,21,2,21,4,21,1105,1,0")
+        .unwrap();
+    assert_eq!(machine.try_move(Direction::East).unwrap(), Reply::Wall);
+    assert_eq!(
+        machine.try_move(Direction::North).unwrap(),
+        Reply::FoundOxygen
+    );
+}
@@ -0,0 +1,411 @@
+use crate::ocr;
+use crate::raster::{Raster, Rgb};
+
+use std::fmt;
+
+pub const BLACK: u8 = 0;
+pub const WHITE: u8 = 1;
+pub const TRANSPARENT: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A layer would have no pixels in it.
+    EmptyLayers,
+    /// A layer would have more pixels than can be counted.
+    TooLarge,
+    /// The character at index `pos` of the parsed text, counting
+    /// whitespace, isn't a pixel colour.
+    InvalidPixel {
+        pos: usize,
+        found: char,
+    },
+    /// Pixel number `pixel`, counting from the start of the first layer,
+    /// has a colour other than black, white or transparent.
+    InvalidColor {
+        pixel: usize,
+        color: u8,
+    },
+    /// Layer number `layer` has `len` pixels rather than a full layer's
+    /// worth.
+    IncompleteLayer {
+        layer: usize,
+        len: usize,
+    },
+    NoLayers,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::EmptyLayers => write!(f, "layers must have at least one pixel"),
+            Error::TooLarge => write!(f, "layers are too large"),
+            Error::InvalidPixel { pos, found } => {
+                write!(f, "`{}` at character {} is not a pixel colour", found, pos)
+            }
+            Error::InvalidColor { pixel, color } => {
+                write!(f, "pixel {} has colour {}, not 0, 1 or 2", pixel, color)
+            }
+            Error::IncompleteLayer { layer, len } => {
+                write!(f, "layer {} has {} pixels, not a whole layer", layer, len)
+            }
+            Error::NoLayers => write!(f, "there are no layers"),
+        }
+    }
+}
+
+/// An image in the Space Image Format: layers of `width` by `height`
+/// pixels, stacked with the first layer in front.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    layers: Vec<Vec<u8>>,
+}
+
+/// How many pixels of each colour a layer has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LayerStats {
+    pub black: usize,
+    pub white: usize,
+    pub transparent: usize,
+}
+
+impl LayerStats {
+    pub fn of(layer: &[u8]) -> Self {
+        let mut stats = Self::default();
+        for pixel in layer {
+            match *pixel {
+                BLACK => stats.black += 1,
+                WHITE => stats.white += 1,
+                _ => stats.transparent += 1,
+            }
+        }
+        stats
+    }
+
+    /// The white pixels times the transparent ones, as asked for to check
+    /// an image isn't corrupted.
+    pub fn checksum(&self) -> usize {
+        self.white * self.transparent
+    }
+
+    pub fn opaque(&self) -> usize {
+        self.black + self.white
+    }
+}
+
+impl Image {
+    /// Parses a string of pixel digits, ignoring whitespace.
+    pub fn parse(width: usize, height: usize, data: &str) -> Result<Self, Error> {
+        let size = layer_size(width, height)?;
+        let pixels = data
+            .chars()
+            .enumerate()
+            .filter(|(_, c)| !c.is_whitespace())
+            .map(|(pos, c)| parse_pixel(pos, c))
+            .collect::<Result<Vec<u8>, Error>>()?;
+
+        let layers = pixels.chunks(size).map(|layer| layer.to_vec()).collect();
+        Self::from_layers(width, height, layers)
+    }
+
+    /// Parses layers drawn as rows of pixel digits, with blank lines
+    /// between them. Each layer has to be complete on its own.
+    pub fn parse_drawn(width: usize, height: usize, drawing: &str) -> Result<Self, Error> {
+        layer_size(width, height)?;
+        let mut layers = Vec::new();
+        let mut layer = Vec::new();
+        let mut pos = 0;
+        for line in drawing.split_inclusive('\n') {
+            if line.trim().is_empty() {
+                if !layer.is_empty() {
+                    layers.push(std::mem::take(&mut layer));
+                }
+            } else {
+                for (i, c) in line.chars().enumerate() {
+                    if !c.is_whitespace() {
+                        layer.push(parse_pixel(pos + i, c)?);
+                    }
+                }
+            }
+            pos += line.chars().count();
+        }
+        if !layer.is_empty() {
+            layers.push(layer);
+        }
+
+        Self::from_layers(width, height, layers)
+    }
+
+    /// Builds an image out of whole layers of pixels.
+    pub fn from_layers(width: usize, height: usize, layers: Vec<Vec<u8>>) -> Result<Self, Error> {
+        let size = layer_size(width, height)?;
+        if layers.is_empty() {
+            return Err(Error::NoLayers);
+        }
+        for (i, layer) in layers.iter().enumerate() {
+            check_layer(size, i, layer)?;
+        }
+
+        Ok(Self {
+            width,
+            height,
+            layers,
+        })
+    }
+
+    /// A single layer image showing `pixels`, which are laid out a row at a
+    /// time.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<u8>) -> Result<Self, Error> {
+        Self::from_layers(width, height, vec![pixels])
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn layers(&self) -> &[Vec<u8>] {
+        &self.layers
+    }
+
+    /// Puts `layer` behind the existing ones.
+    pub fn push_layer(&mut self, layer: Vec<u8>) -> Result<(), Error> {
+        check_layer(self.width * self.height, self.layers.len(), &layer)?;
+        self.layers.push(layer);
+        Ok(())
+    }
+
+    /// Writes the image back out as a string of digits.
+    pub fn encode(&self) -> String {
+        self.layers
+            .iter()
+            .flatten()
+            .map(|pixel| char::from(b'0' + pixel))
+            .collect()
+    }
+
+    pub fn stats(&self) -> Vec<LayerStats> {
+        self.layers
+            .iter()
+            .map(|layer| LayerStats::of(layer))
+            .collect()
+    }
+
+    /// The layer with the fewest black pixels, the first if there's a tie.
+    pub fn fewest_black(&self) -> (usize, LayerStats) {
+        self.stats()
+            .into_iter()
+            .enumerate()
+            .min_by_key(|(_, stats)| stats.black)
+            .unwrap()
+    }
+
+    /// The colour of each pixel with the layers stacked up, transparent
+    /// where every layer is.
+    pub fn decode(&self) -> Vec<u8> {
+        (0..self.width * self.height)
+            .map(|i| match self.visible_layer(i) {
+                Some(layer) => self.layers[layer][i],
+                None => TRANSPARENT,
+            })
+            .collect()
+    }
+
+    /// Which layer the pixel at index `i` is seen on, if any.
+    pub fn visible_layer(&self, i: usize) -> Option<usize> {
+        self.layers.iter().position(|layer| layer[i] != TRANSPARENT)
+    }
+
+    /// Reads the letters in the decoded image, white pixels being lit.
+    pub fn recognize(&self) -> Option<String> {
+        let rows: Vec<Vec<bool>> = self
+            .decode()
+            .chunks(self.width)
+            .map(|row| row.iter().map(|pixel| *pixel == WHITE).collect())
+            .collect();
+        ocr::recognize_pixels(&rows)
+    }
+
+    /// Draws the decoded image, with each pixel `scale` pixels square and
+    /// coloured by `color`.
+    pub fn to_raster<F>(&self, scale: usize, color: F) -> Raster
+    where
+        F: Fn(u8) -> Rgb,
+    {
+        let decoded = self.decode();
+        let mut raster = Raster::new(self.width, self.height, [0, 0, 0]);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                raster.set(x, y, color(decoded[y * self.width + x]));
+            }
+        }
+        raster.scaled(scale)
+    }
+}
+
+fn layer_size(width: usize, height: usize) -> Result<usize, Error> {
+    match width.checked_mul(height) {
+        Some(0) => Err(Error::EmptyLayers),
+        Some(size) => Ok(size),
+        None => Err(Error::TooLarge),
+    }
+}
+
+fn parse_pixel(pos: usize, c: char) -> Result<u8, Error> {
+    match c.to_digit(10) {
+        Some(d) if d <= TRANSPARENT as u32 => Ok(d as u8),
+        _ => Err(Error::InvalidPixel { pos, found: c }),
+    }
+}
+
+fn check_layer(size: usize, i: usize, layer: &[u8]) -> Result<(), Error> {
+    if layer.len() != size {
+        return Err(Error::IncompleteLayer {
+            layer: i,
+            len: layer.len(),
+        });
+    }
+    match layer.iter().position(|pixel| *pixel > TRANSPARENT) {
+        Some(pos) => Err(Error::InvalidColor {
+            pixel: i * size + pos,
+            color: layer[pos],
+        }),
+        None => Ok(()),
+    }
+}
+
+impl fmt::Display for Image {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self
+            .decode()
+            .chunks(self.width)
+            .map(|row| {
+                row.iter()
+                    .map(|pixel| match *pixel {
+                        BLACK => ' ',
+                        WHITE => '█',
+                        _ => '2',
+                    })
+                    .collect()
+            })
+            .collect();
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[test]
+fn decodes_the_examples() {
+    let image = Image::parse(3, 2, "123456789012");
+    assert_eq!(image, Err(Error::InvalidPixel { pos: 2, found: '3' }));
+
+    let image = Image::parse(2, 2, "0222112222120000").unwrap();
+    assert_eq!(image.layers().len(), 4);
+    assert_eq!(image.decode(), vec![0, 1, 1, 0]);
+    assert_eq!(image.to_string(), " █\n█ ");
+    assert_eq!(image.visible_layer(1), Some(1));
+
+    let image = Image::parse(2, 2, "2222 2122 0022").unwrap();
+    assert_eq!(image.decode(), vec![0, 1, 2, 2]);
+    assert_eq!(image.visible_layer(3), None);
+
+    let image = Image::parse(
+        3,
+        3,
+        "
+		122
+		201
+		211
+
+		002
+		111
+		222
+
+		000
+		000
+		000
+		",
+    )
+    .unwrap();
+    assert_eq!(image.decode(), vec![1, 0, 0, 1, 0, 1, 0, 1, 1]);
+}
+
+#[test]
+fn reports_incomplete_layers() {
+    assert_eq!(
+        Image::parse(2, 2, "0222112"),
+        Err(Error::IncompleteLayer { layer: 1, len: 3 })
+    );
+    assert_eq!(Image::parse(2, 2, "  "), Err(Error::NoLayers));
+    assert_eq!(Image::parse(0, 2, "0"), Err(Error::EmptyLayers));
+    assert_eq!(Image::parse(usize::MAX, 2, "0"), Err(Error::TooLarge));
+}
+
+#[test]
+fn points_at_bad_characters() {
+    // Positions count the whitespace in the input
+    assert_eq!(
+        Image::parse(2, 2, "01\n21\n\n0x"),
+        Err(Error::InvalidPixel { pos: 8, found: 'x' })
+    );
+    assert_eq!(
+        Image::parse_drawn(2, 2, "01\n21\n\n0x"),
+        Err(Error::InvalidPixel { pos: 8, found: 'x' })
+    );
+    assert_eq!(
+        Error::InvalidPixel { pos: 8, found: 'x' }.to_string(),
+        "`x` at character 8 is not a pixel colour"
+    );
+}
+
+#[test]
+fn parses_drawn_layers() {
+    let image = Image::parse_drawn(2, 2, "02\n22\n\n11\n22\n\n\n22\n12\n").unwrap();
+    assert_eq!(image.encode(), "022211222212");
+
+    // Layers are split on blank lines, not by counting pixels
+    assert_eq!(
+        Image::parse_drawn(2, 2, "022\n\n11222\n"),
+        Err(Error::IncompleteLayer { layer: 0, len: 3 })
+    );
+}
+
+#[test]
+fn counts_pixels_per_layer() {
+    let image = Image::parse(3, 2, "001122012012200000").unwrap();
+    let stats = image.stats();
+    assert_eq!(
+        stats[0],
+        LayerStats {
+            black: 2,
+            white: 2,
+            transparent: 2
+        }
+    );
+    assert_eq!(stats[2].opaque(), 5);
+
+    let (layer, stats) = image.fewest_black();
+    assert_eq!(layer, 0);
+    assert_eq!(stats.checksum(), 4);
+}
+
+#[test]
+fn encodes_layers() {
+    let mut image = Image::from_pixels(2, 1, vec![2, 1]).unwrap();
+    image.push_layer(vec![0, 0]).unwrap();
+    assert_eq!(image.encode(), "2100");
+    assert_eq!(
+        image.push_layer(vec![0]),
+        Err(Error::IncompleteLayer { layer: 2, len: 1 })
+    );
+    assert_eq!(
+        image.push_layer(vec![0, 7]),
+        Err(Error::InvalidColor { pixel: 5, color: 7 })
+    );
+    assert_eq!(image.layers().len(), 2);
+    assert_eq!(Image::parse(2, 1, &image.encode()), Ok(image));
+}